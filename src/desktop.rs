@@ -0,0 +1,343 @@
+//! Virtual desktops, for `SwitchDesktop` and `SendToDesktop`.
+//!
+//! Windows has no public API for switching desktops, nor for moving another
+//! process's window to one: `IVirtualDesktopManager` only moves windows of
+//! the calling process. Explorer's own `IVirtualDesktopManagerInternal` does
+//! both, but it's undocumented and its IIDs change between Windows builds, so
+//! it's used when the build matches the one declared here. Otherwise, since
+//! Explorer lists the desktops in order in the registry and switches on
+//! Ctrl+Win+Left/Right, switching presses those keys as often as needed, and
+//! a window is moved by hiding it, switching, and showing it again, which
+//! puts it on the desktop that is current by then.
+
+use std::{ffi::c_void, mem::size_of};
+
+use windows::{
+    Win32::{
+        Foundation::HWND,
+        System::{
+            Com::{
+                CLSCTX_ALL, CLSCTX_LOCAL_SERVER, COINIT_APARTMENTTHREADED, CoCreateInstance,
+                CoInitializeEx, IServiceProvider,
+            },
+            RemoteDesktop::ProcessIdToSessionId,
+            Threading::GetCurrentProcessId,
+        },
+        UI::{
+            Input::KeyboardAndMouse::*,
+            Shell::{Common::IObjectArray, IVirtualDesktopManager, VirtualDesktopManager},
+        },
+    },
+    core::{GUID, HRESULT, IUnknown, IUnknown_Vtbl, Interface, interface},
+};
+use winreg::{RegKey, enums::HKEY_CURRENT_USER};
+
+use crate::hooks;
+
+// The `interface` macro refers to the crate by this name
+use windows::core as windows_core;
+
+const EXPLORER_KEY: &str = r"Software\Microsoft\Windows\CurrentVersion\Explorer";
+
+/// Explorer's service provider for the interfaces below
+const CLSID_IMMERSIVE_SHELL: GUID = GUID::from_u128(0xC2F03A33_21F5_47FA_B4BB_156362A2F239);
+
+const SID_VIRTUAL_DESKTOP_MANAGER_INTERNAL: GUID =
+    GUID::from_u128(0xC5E0CDCA_7B6E_41B2_9FC4_D93975CC467B);
+
+const SID_VIRTUAL_DESKTOP_PINNED_APPS: GUID =
+    GUID::from_u128(0xB5A399E7_1C87_46B8_88E9_FC5747B171BD);
+
+/// A top-level window as Explorer tracks it. Only ever passed back to
+/// Explorer, so none of its methods are declared.
+#[interface("372E1D3B-38D3-42E4-A15B-8AB2B178F513")]
+unsafe trait IApplicationView: IUnknown {}
+
+/// Only passed back to Explorer, like `IApplicationView`
+#[interface("3F07F4BE-B107-441A-AF0F-39D82529072C")]
+unsafe trait IVirtualDesktop: IUnknown {}
+
+#[interface("1841C6D7-4F9D-42C0-AF41-8747538F10E5")]
+unsafe trait IApplicationViewCollection: IUnknown {
+    unsafe fn GetViews(&self, views: *mut *mut c_void) -> HRESULT;
+    unsafe fn GetViewsByZOrder(&self, views: *mut *mut c_void) -> HRESULT;
+    unsafe fn GetViewsByAppUserModelId(&self, id: *const u16, views: *mut *mut c_void) -> HRESULT;
+    unsafe fn GetViewForHwnd(&self, hwnd: HWND, view: *mut Option<IApplicationView>) -> HRESULT;
+}
+
+/// Unchanged since Windows 10
+#[interface("4CE81583-1E4C-4632-A621-07A53543148F")]
+unsafe trait IVirtualDesktopPinnedApps: IUnknown {
+    unsafe fn IsAppIdPinned(&self, id: *const u16, pinned: *mut i32) -> HRESULT;
+    unsafe fn PinAppID(&self, id: *const u16) -> HRESULT;
+    unsafe fn UnpinAppID(&self, id: *const u16) -> HRESULT;
+    unsafe fn IsViewPinned(&self, view: *mut c_void, pinned: *mut i32) -> HRESULT;
+    unsafe fn PinView(&self, view: *mut c_void) -> HRESULT;
+    unsafe fn UnpinView(&self, view: *mut c_void) -> HRESULT;
+}
+
+/// As of Windows 11 24H2. Only the methods up to `SwitchDesktop` are
+/// declared, which is as far as the vtable is read.
+#[interface("53F5CA0B-158F-4124-900C-057158060B27")]
+unsafe trait IVirtualDesktopManagerInternal: IUnknown {
+    unsafe fn GetCount(&self, count: *mut i32) -> HRESULT;
+    unsafe fn MoveViewToDesktop(&self, view: *mut c_void, desktop: *mut c_void) -> HRESULT;
+    unsafe fn CanViewMoveDesktops(&self, view: *mut c_void, can: *mut i32) -> HRESULT;
+    unsafe fn GetCurrentDesktop(&self, desktop: *mut Option<IVirtualDesktop>) -> HRESULT;
+    unsafe fn GetDesktops(&self, desktops: *mut Option<IObjectArray>) -> HRESULT;
+    unsafe fn GetAdjacentDesktop(
+        &self,
+        from: *mut c_void,
+        direction: i32,
+        desktop: *mut Option<IVirtualDesktop>,
+    ) -> HRESULT;
+    unsafe fn SwitchDesktop(&self, desktop: *mut c_void) -> HRESULT;
+}
+
+/// Id of a desktop, a GUID as stored in the registry
+pub type DesktopId = [u8; 16];
+
+/// Key with no meaning, pressed so that releasing Alt doesn't open the menu
+/// bar of the focused window
+const VK_NONE: VIRTUAL_KEY = VIRTUAL_KEY(0xE8);
+
+/// Ids of the desktops, in the order Task View shows them
+pub fn desktops() -> Vec<DesktopId> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+
+    let Ok(value) = hkcu
+        .open_subkey(format!(r"{EXPLORER_KEY}\VirtualDesktops"))
+        .and_then(|key| key.get_raw_value("VirtualDesktopIDs"))
+    else {
+        return Vec::new();
+    };
+
+    value
+        .bytes
+        .chunks_exact(16)
+        .filter_map(|id| id.try_into().ok())
+        .collect()
+}
+
+/// Position of the current desktop among `desktops()`
+pub unsafe fn current() -> Option<usize> {
+    let id = current_id()?;
+
+    desktops().iter().position(|desktop| *desktop == id)
+}
+
+unsafe fn current_id() -> Option<DesktopId> {
+    let hkcu = RegKey::predef(HKEY_CURRENT_USER);
+
+    let mut session = 0;
+    let _ = ProcessIdToSessionId(GetCurrentProcessId(), &mut session);
+
+    // Windows 11 keeps it next to the list, Windows 10 per session
+    let paths = [
+        format!(r"{EXPLORER_KEY}\VirtualDesktops"),
+        format!(r"{EXPLORER_KEY}\SessionInfo\{session}\VirtualDesktops"),
+    ];
+
+    paths.iter().find_map(|path| {
+        let value = hkcu
+            .open_subkey(path)
+            .and_then(|key| key.get_raw_value("CurrentVirtualDesktop"))
+            .ok()?;
+
+        value.bytes.as_slice().try_into().ok()
+    })
+}
+
+/// Desktop a window is on
+pub unsafe fn window_desktop(hwnd: HWND) -> Option<DesktopId> {
+    // Fails harmlessly when COM is already set up on this thread
+    let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+    let manager: IVirtualDesktopManager =
+        CoCreateInstance(&VirtualDesktopManager, None, CLSCTX_ALL).ok()?;
+
+    manager.GetWindowDesktopId(hwnd).ok().map(guid_bytes)
+}
+
+/// Switch to the desktop at `index` among `desktops()`. Returns false if
+/// there is no such desktop.
+pub unsafe fn switch_to(index: usize) -> bool {
+    if let Some((manager, desktop)) = internal_desktop(index) {
+        return manager.SwitchDesktop(desktop.as_raw()).is_ok();
+    }
+
+    let Some(current) = current() else {
+        return false;
+    };
+
+    if index >= desktops().len() {
+        return false;
+    }
+
+    let arrow = match index > current {
+        true => VK_RIGHT,
+        false => VK_LEFT,
+    };
+
+    release_modifiers();
+
+    for _ in 0..index.abs_diff(current) {
+        press(&[VK_LCONTROL, VK_LWIN, arrow]);
+    }
+
+    true
+}
+
+/// Move a window to the desktop at `index` among `desktops()`, without
+/// switching there. Returns false if that takes Explorer's internal
+/// interface and it isn't available, which leaves hiding the window and
+/// switching to the caller.
+pub unsafe fn move_to(hwnd: HWND, index: usize) -> bool {
+    let Some((manager, desktop)) = internal_desktop(index) else {
+        return false;
+    };
+
+    let Some(view) = application_view(hwnd) else {
+        return false;
+    };
+
+    manager
+        .MoveViewToDesktop(view.as_raw(), desktop.as_raw())
+        .is_ok()
+}
+
+/// Show a window on every desktop, or only on the current one again, as
+/// Task View's "Show this window on all desktops" does. Returns whether it's
+/// pinned now.
+pub unsafe fn toggle_pinned(hwnd: HWND) -> Result<bool, String> {
+    let apps: IVirtualDesktopPinnedApps = shell_service(&SID_VIRTUAL_DESKTOP_PINNED_APPS)
+        .ok_or("Explorer's IVirtualDesktopPinnedApps interface isn't available")?;
+
+    let view =
+        application_view(hwnd).ok_or("Explorer doesn't track the window as an app window")?;
+
+    let mut pinned = 0;
+    apps.IsViewPinned(view.as_raw(), &mut pinned)
+        .ok()
+        .map_err(|err| format!("Failed to check if the window is pinned: {err}"))?;
+
+    match pinned != 0 {
+        true => apps.UnpinView(view.as_raw()),
+        false => apps.PinView(view.as_raw()),
+    }
+    .ok()
+    .map_err(|err| format!("Failed to pin or unpin the window: {err}"))?;
+
+    Ok(pinned == 0)
+}
+
+/// Explorer's desktop manager and the desktop at `index`, if this Windows
+/// build has the interface as declared
+unsafe fn internal_desktop(
+    index: usize,
+) -> Option<(IVirtualDesktopManagerInternal, IVirtualDesktop)> {
+    let manager: IVirtualDesktopManagerInternal =
+        shell_service(&SID_VIRTUAL_DESKTOP_MANAGER_INTERNAL)?;
+
+    let mut desktops = None;
+
+    if manager.GetDesktops(&mut desktops).is_err() {
+        return None;
+    }
+
+    let desktop = desktops?.GetAt(index as u32).ok()?;
+
+    Some((manager, desktop))
+}
+
+unsafe fn application_view(hwnd: HWND) -> Option<IApplicationView> {
+    let views: IApplicationViewCollection = shell_service(&IApplicationViewCollection::IID)?;
+    let mut view = None;
+
+    if views.GetViewForHwnd(hwnd, &mut view).is_err() {
+        return None;
+    }
+
+    view
+}
+
+/// Query Explorer for one of its services, which fails when the interface's
+/// IID doesn't match the running build
+unsafe fn shell_service<T: Interface>(service: &GUID) -> Option<T> {
+    // Fails harmlessly when COM is already set up on this thread
+    let _ = CoInitializeEx(None, COINIT_APARTMENTTHREADED);
+
+    let shell: IServiceProvider =
+        CoCreateInstance(&CLSID_IMMERSIVE_SHELL, None, CLSCTX_LOCAL_SERVER).ok()?;
+
+    shell.QueryService(service).ok()
+}
+
+/// Let go of the modifiers held for the keybind, which would otherwise join
+/// in the keys pressed here
+unsafe fn release_modifiers() {
+    let mods = hooks::current_modifiers();
+    let mut inputs = vec![key_input(VK_NONE, false), key_input(VK_NONE, true)];
+
+    for (flag, keys) in [
+        (MOD_ALT, [VK_LMENU, VK_RMENU]),
+        (MOD_SHIFT, [VK_LSHIFT, VK_RSHIFT]),
+        (MOD_CONTROL, [VK_LCONTROL, VK_RCONTROL]),
+        (MOD_WIN, [VK_LWIN, VK_RWIN]),
+    ] {
+        if mods.contains(flag) {
+            inputs.extend(keys.map(|key| key_input(key, true)));
+        }
+    }
+
+    SendInput(&inputs, size_of::<INPUT>() as i32);
+}
+
+/// Press the keys in order and release them in reverse
+unsafe fn press(keys: &[VIRTUAL_KEY]) {
+    let downs = keys.iter().map(|&key| key_input(key, false));
+    let ups = keys.iter().rev().map(|&key| key_input(key, true));
+    let inputs: Vec<INPUT> = downs.chain(ups).collect();
+
+    SendInput(&inputs, size_of::<INPUT>() as i32);
+}
+
+fn key_input(key: VIRTUAL_KEY, up: bool) -> INPUT {
+    let mut flags = KEYBD_EVENT_FLAGS(0);
+
+    if up {
+        flags |= KEYEVENTF_KEYUP;
+    }
+
+    // The arrow keys, the Windows keys and the right-hand modifiers are
+    // extended keys
+    if matches!(
+        key,
+        VK_LEFT | VK_RIGHT | VK_LWIN | VK_RWIN | VK_RMENU | VK_RCONTROL
+    ) {
+        flags |= KEYEVENTF_EXTENDEDKEY;
+    }
+
+    INPUT {
+        r#type: INPUT_KEYBOARD,
+        Anonymous: INPUT_0 {
+            ki: KEYBDINPUT {
+                wVk: key,
+                dwFlags: flags,
+                ..Default::default()
+            },
+        },
+    }
+}
+
+/// A GUID laid out as the registry stores it
+fn guid_bytes(guid: GUID) -> DesktopId {
+    let mut bytes = [0; 16];
+
+    bytes[..4].copy_from_slice(&guid.data1.to_le_bytes());
+    bytes[4..6].copy_from_slice(&guid.data2.to_le_bytes());
+    bytes[6..8].copy_from_slice(&guid.data3.to_le_bytes());
+    bytes[8..].copy_from_slice(&guid.data4);
+
+    bytes
+}
@@ -0,0 +1,90 @@
+//! Friendly names of monitors, e.g. `DELL U2720Q`, as the display settings
+//! show them. They come from the monitor's EDID, so unlike monitor numbers
+//! and device names they don't change between reboots and dock events.
+
+use std::{collections::HashMap, mem::size_of};
+
+use windows::Win32::{
+    Devices::Display::{
+        DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME, DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+        DISPLAYCONFIG_DEVICE_INFO_HEADER, DISPLAYCONFIG_MODE_INFO, DISPLAYCONFIG_PATH_INFO,
+        DISPLAYCONFIG_SOURCE_DEVICE_NAME, DISPLAYCONFIG_TARGET_DEVICE_NAME,
+        DisplayConfigGetDeviceInfo, GetDisplayConfigBufferSizes, QDC_ONLY_ACTIVE_PATHS,
+        QueryDisplayConfig,
+    },
+    Foundation::ERROR_SUCCESS,
+};
+
+/// Friendly name of each active monitor, by device name without the `\\.\`
+/// prefix, e.g. `DISPLAY1`
+pub unsafe fn friendly_names() -> HashMap<String, String> {
+    let mut names = HashMap::new();
+
+    let mut path_count = 0;
+    let mut mode_count = 0;
+
+    if GetDisplayConfigBufferSizes(QDC_ONLY_ACTIVE_PATHS, &mut path_count, &mut mode_count)
+        != ERROR_SUCCESS
+    {
+        return names;
+    }
+
+    let mut paths = vec![DISPLAYCONFIG_PATH_INFO::default(); path_count as usize];
+    let mut modes = vec![DISPLAYCONFIG_MODE_INFO::default(); mode_count as usize];
+
+    if QueryDisplayConfig(
+        QDC_ONLY_ACTIVE_PATHS,
+        &mut path_count,
+        paths.as_mut_ptr(),
+        &mut mode_count,
+        modes.as_mut_ptr(),
+        None,
+    ) != ERROR_SUCCESS
+    {
+        return names;
+    }
+
+    for path in &paths[..path_count as usize] {
+        let mut source = DISPLAYCONFIG_SOURCE_DEVICE_NAME {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_SOURCE_NAME,
+                size: size_of::<DISPLAYCONFIG_SOURCE_DEVICE_NAME>() as u32,
+                adapterId: path.sourceInfo.adapterId,
+                id: path.sourceInfo.id,
+            },
+            ..Default::default()
+        };
+
+        let mut target = DISPLAYCONFIG_TARGET_DEVICE_NAME {
+            header: DISPLAYCONFIG_DEVICE_INFO_HEADER {
+                r#type: DISPLAYCONFIG_DEVICE_INFO_GET_TARGET_NAME,
+                size: size_of::<DISPLAYCONFIG_TARGET_DEVICE_NAME>() as u32,
+                adapterId: path.targetInfo.adapterId,
+                id: path.targetInfo.id,
+            },
+            ..Default::default()
+        };
+
+        if DisplayConfigGetDeviceInfo(&mut source.header) != 0
+            || DisplayConfigGetDeviceInfo(&mut target.header) != 0
+        {
+            continue;
+        }
+
+        let device = from_wide(&source.viewGdiDeviceName);
+        let friendly = from_wide(&target.monitorFriendlyDeviceName);
+
+        // Built-in panels often have none
+        if !friendly.is_empty() {
+            names.insert(device.trim_start_matches(r"\\.\").to_owned(), friendly);
+        }
+    }
+
+    names
+}
+
+fn from_wide(buf: &[u16]) -> String {
+    let len = buf.iter().position(|&c| c == 0).unwrap_or(buf.len());
+
+    String::from_utf16_lossy(&buf[..len])
+}
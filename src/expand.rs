@@ -0,0 +1,112 @@
+//! Environment variables in config values.
+//!
+//! Both Windows style `%VAR%` and shell style `${VAR}` are expanded, and a
+//! leading `~` stands for the user's home directory. Variables that aren't set
+//! are left as written, so a lone `%` doesn't disappear.
+
+use std::env;
+
+/// Expand environment variables and a leading `~` in `value`
+pub fn expand(value: &str) -> String {
+    let value = expand_home(value);
+
+    let mut out = String::with_capacity(value.len());
+    let mut rest = value.as_str();
+
+    while let Some(start) = rest.find(['%', '$']) {
+        out.push_str(&rest[..start]);
+        rest = &rest[start..];
+
+        let var = if let Some(after) = rest.strip_prefix('%') {
+            after.find('%').map(|end| (&after[..end], end + 2))
+        } else if let Some(after) = rest.strip_prefix("${") {
+            after.find('}').map(|end| (&after[..end], end + 3))
+        } else {
+            None
+        };
+
+        match var.and_then(|(name, len)| Some((env::var(name).ok()?, len))) {
+            Some((var, len)) => {
+                out.push_str(&var);
+                rest = &rest[len..];
+            }
+            None => {
+                out.push_str(&rest[..1]);
+                rest = &rest[1..];
+            }
+        }
+    }
+
+    out.push_str(rest);
+
+    out
+}
+
+fn expand_home(value: &str) -> String {
+    let Some(rest) = value.strip_prefix('~') else {
+        return value.to_owned();
+    };
+
+    if !(rest.is_empty() || rest.starts_with(['/', '\\'])) {
+        return value.to_owned();
+    }
+
+    match env::var("USERPROFILE").or_else(|_| env::var("HOME")) {
+        Ok(home) => format!("{home}{rest}"),
+        Err(_) => value.to_owned(),
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    /// Set a variable only these tests use, so they can run in parallel
+    fn set(name: &str, value: &str) {
+        // SAFETY: Windows guards the environment with a lock, so setting it
+        // while other tests read it is sound there
+        unsafe { env::set_var(name, value) };
+    }
+
+    #[test]
+    fn expands_windows_style_variables() {
+        set("WINMGR_TEST_PERCENT", "C:\\Tools");
+
+        assert_eq!(
+            expand("%WINMGR_TEST_PERCENT%\\app.exe"),
+            "C:\\Tools\\app.exe"
+        );
+    }
+
+    #[test]
+    fn expands_shell_style_variables() {
+        set("WINMGR_TEST_BRACES", "/opt");
+
+        assert_eq!(expand("${WINMGR_TEST_BRACES}/app"), "/opt/app");
+    }
+
+    #[test]
+    fn leaves_unset_variables_as_written() {
+        assert_eq!(
+            expand("%WINMGR_TEST_UNSET%\\${WINMGR_TEST_UNSET}"),
+            "%WINMGR_TEST_UNSET%\\${WINMGR_TEST_UNSET}"
+        );
+    }
+
+    #[test]
+    fn keeps_lone_markers() {
+        assert_eq!(expand("100% sure, $5"), "100% sure, $5");
+    }
+
+    #[test]
+    fn expands_home_only_as_a_whole_segment() {
+        let home = env::var("USERPROFILE")
+            .or_else(|_| env::var("HOME"))
+            .unwrap();
+
+        assert_eq!(expand("~"), home);
+        assert_eq!(expand("~\\bin"), format!("{home}\\bin"));
+        assert_eq!(expand("~user/bin"), "~user/bin");
+        assert_eq!(expand("a~b"), "a~b");
+    }
+}
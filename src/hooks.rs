@@ -0,0 +1,392 @@
+//! Low-level input hooks for bindings that `RegisterHotKey` can't express,
+//! and event hooks following focus changes for `FocusLast` and windows
+//! opening and closing.
+//!
+//! Hook procedures run on the thread that installed them, inside its message
+//! loop, so they only record the match and post a message back to that loop.
+
+use std::cell::{Cell, RefCell};
+
+use nohash_hasher::IntSet;
+
+use windows::Win32::{
+    Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+    System::{LibraryLoader::GetModuleHandleW, Threading::GetCurrentThreadId},
+    UI::{
+        Accessibility::{HWINEVENTHOOK, SetWinEventHook},
+        Input::KeyboardAndMouse::*,
+        WindowsAndMessaging::{
+            CHILDID_SELF, CallNextHookEx, EVENT_OBJECT_DESTROY, EVENT_OBJECT_SHOW,
+            EVENT_SYSTEM_FOREGROUND, GetForegroundWindow, HHOOK, KBDLLHOOKSTRUCT, MSLLHOOKSTRUCT,
+            OBJID_WINDOW, PostThreadMessageW, SetWindowsHookExW, UnhookWindowsHookEx,
+            WH_KEYBOARD_LL, WH_MOUSE_LL, WINEVENT_OUTOFCONTEXT, WINEVENT_SKIPOWNPROCESS, WM_APP,
+            WM_KEYDOWN, WM_KEYUP, WM_LBUTTONDOWN, WM_LBUTTONUP, WM_MBUTTONDOWN, WM_MBUTTONUP,
+            WM_RBUTTONDOWN, WM_RBUTTONUP, WM_SYSKEYDOWN, WM_SYSKEYUP, WM_XBUTTONDOWN, WM_XBUTTONUP,
+        },
+    },
+};
+
+use crate::{MouseButton, window};
+
+/// Posted to the installing thread when a mouse binding matches.
+/// `wParam` is the index of the binding.
+pub const WM_APP_MOUSEBIND: u32 = WM_APP + 1;
+
+/// Posted to the installing thread when a modifier is double-tapped.
+/// `wParam` is the index of the gesture.
+pub const WM_APP_GESTURE: u32 = WM_APP + 2;
+
+/// Posted to the installing thread when an app window is shown for the first
+/// time. `wParam` is the window handle.
+pub const WM_APP_WINDOW_SHOWN: u32 = WM_APP + 8;
+
+/// Posted to the installing thread when an app window is destroyed.
+/// `wParam` is the window handle, which may already belong to a new window.
+pub const WM_APP_WINDOW_DESTROYED: u32 = WM_APP + 9;
+
+thread_local! {
+    static MOUSE_HOOK: Cell<Option<HHOOK>> = const { Cell::new(None) };
+    static MOUSE_BINDS: RefCell<Vec<Option<(HOT_KEY_MODIFIERS, MouseButton)>>> = const { RefCell::new(Vec::new()) };
+    /// Button whose press was consumed by a binding, so its release is consumed too
+    static SWALLOWED: Cell<Option<MouseButton>> = const { Cell::new(None) };
+
+    static KEYBOARD_HOOK: Cell<Option<HHOOK>> = const { Cell::new(None) };
+    static GESTURES: RefCell<Vec<Option<HOT_KEY_MODIFIERS>>> = const { RefCell::new(Vec::new()) };
+    static DOUBLE_TAP_MS: Cell<u32> = const { Cell::new(0) };
+    static TAP: Cell<TapState> = const { Cell::new(TapState { held: None, last: None }) };
+
+    /// App windows that have had focus, most recently focused first
+    static FOCUS: RefCell<Vec<HWND>> = const { RefCell::new(Vec::new()) };
+    /// App windows that have been shown and not destroyed since
+    static OPEN: RefCell<IntSet<isize>> = RefCell::new(IntSet::default());
+}
+
+#[derive(Debug, Clone, Copy)]
+struct TapState {
+    /// Modifier held down with no other key pressed since
+    held: Option<HOT_KEY_MODIFIERS>,
+    /// Modifier tapped last, and the time it was released
+    last: Option<(HOT_KEY_MODIFIERS, u32)>,
+}
+
+/// Replace the active mouse bindings, installing the hook when there are any
+/// and removing it when there are none. Invalid bindings are `None`, so the
+/// others keep their indexes.
+pub unsafe fn set_mouse_binds(binds: Vec<Option<(HOT_KEY_MODIFIERS, MouseButton)>>) {
+    let empty = binds.iter().all(Option::is_none);
+
+    MOUSE_BINDS.set(binds);
+
+    match (MOUSE_HOOK.get(), empty) {
+        (None, false) => {
+            let module = GetModuleHandleW(None).ok();
+
+            match SetWindowsHookExW(WH_MOUSE_LL, Some(mouse_proc), module.map(Into::into), 0) {
+                Ok(hook) => MOUSE_HOOK.set(Some(hook)),
+                Err(err) => eprintln!("Failed to install mouse hook: {err}"),
+            }
+        }
+        (Some(hook), true) => {
+            let _ = UnhookWindowsHookEx(hook);
+            MOUSE_HOOK.set(None);
+        }
+        _ => {}
+    }
+}
+
+/// Replace the active double-tap gestures, installing the keyboard hook when
+/// there are any and removing it when there are none. Invalid gestures are
+/// `None`, so the others keep their indexes.
+pub unsafe fn set_gestures(gestures: Vec<Option<HOT_KEY_MODIFIERS>>, double_tap_ms: u32) {
+    let empty = gestures.iter().all(Option::is_none);
+
+    GESTURES.set(gestures);
+    DOUBLE_TAP_MS.set(double_tap_ms);
+
+    match (KEYBOARD_HOOK.get(), empty) {
+        (None, false) => {
+            let module = GetModuleHandleW(None).ok();
+
+            match SetWindowsHookExW(
+                WH_KEYBOARD_LL,
+                Some(keyboard_proc),
+                module.map(Into::into),
+                0,
+            ) {
+                Ok(hook) => KEYBOARD_HOOK.set(Some(hook)),
+                Err(err) => eprintln!("Failed to install keyboard hook: {err}"),
+            }
+        }
+        (Some(hook), true) => {
+            let _ = UnhookWindowsHookEx(hook);
+            KEYBOARD_HOOK.set(None);
+        }
+        _ => {}
+    }
+}
+
+/// Start following which app window has focus, for `previous_focus`, and
+/// posting `WM_APP_WINDOW_SHOWN` and `WM_APP_WINDOW_DESTROYED` as app windows
+/// come and go. Windows already open count as shown.
+pub unsafe fn watch_windows() {
+    let focused = GetForegroundWindow();
+
+    if window::is_app_window(focused) {
+        FOCUS.set(vec![focused]);
+    }
+
+    OPEN.set(
+        window::app_windows()
+            .into_iter()
+            .map(|hwnd| hwnd.0 as isize)
+            .collect(),
+    );
+
+    let hook = SetWinEventHook(
+        EVENT_SYSTEM_FOREGROUND,
+        EVENT_SYSTEM_FOREGROUND,
+        None,
+        Some(foreground_proc),
+        0,
+        0,
+        WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+    );
+
+    if hook.is_invalid() {
+        eprintln!("Failed to install focus hook");
+    }
+
+    // The two events are adjacent, so one hook covers both
+    let hook = SetWinEventHook(
+        EVENT_OBJECT_DESTROY,
+        EVENT_OBJECT_SHOW,
+        None,
+        Some(object_proc),
+        0,
+        0,
+        WINEVENT_OUTOFCONTEXT | WINEVENT_SKIPOWNPROCESS,
+    );
+
+    if hook.is_invalid() {
+        eprintln!("Failed to install window hook");
+    }
+}
+
+/// The app window that had focus before the current one
+pub fn previous_focus() -> Option<HWND> {
+    FOCUS.with_borrow(|focus| focus.get(1).copied())
+}
+
+/// Modifiers currently held down, in `RegisterHotKey` terms
+pub unsafe fn current_modifiers() -> HOT_KEY_MODIFIERS {
+    let pressed = |key: VIRTUAL_KEY| GetAsyncKeyState(key.0 as i32) as u16 & 0x8000 != 0;
+
+    let mut mods = HOT_KEY_MODIFIERS(0);
+
+    if pressed(VK_MENU) {
+        mods |= MOD_ALT;
+    }
+
+    if pressed(VK_CONTROL) {
+        mods |= MOD_CONTROL;
+    }
+
+    if pressed(VK_SHIFT) {
+        mods |= MOD_SHIFT;
+    }
+
+    if pressed(VK_LWIN) || pressed(VK_RWIN) {
+        mods |= MOD_WIN;
+    }
+
+    mods
+}
+
+/// Decode a low-level mouse message into (button, is_press)
+fn button_event(message: u32, mouse_data: u32) -> Option<(MouseButton, bool)> {
+    let xbutton = match (mouse_data >> 16) as u16 {
+        1 => MouseButton::XButton1,
+        _ => MouseButton::XButton2,
+    };
+
+    match message {
+        WM_LBUTTONDOWN => Some((MouseButton::Left, true)),
+        WM_LBUTTONUP => Some((MouseButton::Left, false)),
+        WM_RBUTTONDOWN => Some((MouseButton::Right, true)),
+        WM_RBUTTONUP => Some((MouseButton::Right, false)),
+        WM_MBUTTONDOWN => Some((MouseButton::Middle, true)),
+        WM_MBUTTONUP => Some((MouseButton::Middle, false)),
+        WM_XBUTTONDOWN => Some((xbutton, true)),
+        WM_XBUTTONUP => Some((xbutton, false)),
+        _ => None,
+    }
+}
+
+unsafe extern "system" fn mouse_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        let info = &*(lparam.0 as *const MSLLHOOKSTRUCT);
+
+        match button_event(wparam.0 as u32, info.mouseData) {
+            Some((button, true)) => {
+                let mods = current_modifiers();
+
+                let index = MOUSE_BINDS.with_borrow(|binds| {
+                    binds.iter().position(|bind| *bind == Some((mods, button)))
+                });
+
+                if let Some(index) = index {
+                    SWALLOWED.set(Some(button));
+
+                    let _ = PostThreadMessageW(
+                        GetCurrentThreadId(),
+                        WM_APP_MOUSEBIND,
+                        WPARAM(index),
+                        LPARAM(0),
+                    );
+
+                    return LRESULT(1);
+                }
+            }
+            Some((button, false)) if SWALLOWED.get() == Some(button) => {
+                SWALLOWED.set(None);
+                return LRESULT(1);
+            }
+            _ => {}
+        }
+    }
+
+    CallNextHookEx(None, code, wparam, lparam)
+}
+
+unsafe extern "system" fn foreground_proc(
+    _hook: HWINEVENTHOOK,
+    _event: u32,
+    hwnd: HWND,
+    _object: i32,
+    _child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    // Task switchers, the taskbar and the like pass focus on without counting
+    if window::is_app_window(hwnd) {
+        FOCUS.with_borrow_mut(|focus| {
+            focus.retain(|&other| other != hwnd);
+            focus.insert(0, hwnd);
+        });
+    }
+}
+
+unsafe extern "system" fn object_proc(
+    _hook: HWINEVENTHOOK,
+    event: u32,
+    hwnd: HWND,
+    object: i32,
+    child: i32,
+    _thread: u32,
+    _time: u32,
+) {
+    // The events fire for carets, cursors and controls inside windows too
+    if object != OBJID_WINDOW.0 || child != CHILDID_SELF as i32 {
+        return;
+    }
+
+    let key = hwnd.0 as isize;
+
+    let message = match event {
+        EVENT_OBJECT_SHOW => {
+            // Showing again after being hidden, e.g. by `Scratchpad`, isn't new
+            if !window::is_app_window(hwnd) || !OPEN.with_borrow_mut(|open| open.insert(key)) {
+                return;
+            }
+
+            WM_APP_WINDOW_SHOWN
+        }
+        EVENT_OBJECT_DESTROY => {
+            FOCUS.with_borrow_mut(|focus| focus.retain(|&other| other != hwnd));
+
+            if !OPEN.with_borrow_mut(|open| open.remove(&key)) {
+                return;
+            }
+
+            WM_APP_WINDOW_DESTROYED
+        }
+        _ => return,
+    };
+
+    let _ = PostThreadMessageW(
+        GetCurrentThreadId(),
+        message,
+        WPARAM(key as usize),
+        LPARAM(0),
+    );
+}
+
+/// The modifier a left/right specific virtual key belongs to
+fn key_modifier(key: VIRTUAL_KEY) -> Option<HOT_KEY_MODIFIERS> {
+    match key {
+        VK_LMENU | VK_RMENU => Some(MOD_ALT),
+        VK_LCONTROL | VK_RCONTROL => Some(MOD_CONTROL),
+        VK_LSHIFT | VK_RSHIFT => Some(MOD_SHIFT),
+        VK_LWIN | VK_RWIN => Some(MOD_WIN),
+        _ => None,
+    }
+}
+
+/// Track modifier taps: a press and release with no other key in between.
+/// Two taps of the same modifier within the double-tap window fire a gesture.
+/// Key events are never consumed.
+unsafe extern "system" fn keyboard_proc(code: i32, wparam: WPARAM, lparam: LPARAM) -> LRESULT {
+    if code >= 0 {
+        let info = &*(lparam.0 as *const KBDLLHOOKSTRUCT);
+        let modifier = key_modifier(VIRTUAL_KEY(info.vkCode as u16));
+        let mut tap = TAP.get();
+
+        match (wparam.0 as u32, modifier) {
+            (WM_KEYDOWN | WM_SYSKEYDOWN, Some(modifier)) => {
+                // Held keys auto-repeat their key down
+                if tap.held != Some(modifier) {
+                    tap.held = Some(modifier);
+                    tap.last = tap.last.filter(|(last, _)| *last == modifier);
+                }
+            }
+            (WM_KEYDOWN | WM_SYSKEYDOWN, None) => {
+                tap = TapState {
+                    held: None,
+                    last: None,
+                };
+            }
+            (WM_KEYUP | WM_SYSKEYUP, Some(modifier)) if tap.held == Some(modifier) => {
+                tap.held = None;
+
+                match tap.last {
+                    Some((last, released))
+                        if last == modifier
+                            && info.time.wrapping_sub(released) <= DOUBLE_TAP_MS.get() =>
+                    {
+                        tap.last = None;
+                        post_gesture(modifier);
+                    }
+                    _ => tap.last = Some((modifier, info.time)),
+                }
+            }
+            _ => {}
+        }
+
+        TAP.set(tap);
+    }
+
+    CallNextHookEx(None, code, wparam, lparam)
+}
+
+unsafe fn post_gesture(modifier: HOT_KEY_MODIFIERS) {
+    let index = GESTURES.with_borrow(|gestures| gestures.iter().position(|m| *m == Some(modifier)));
+
+    if let Some(index) = index {
+        let _ = PostThreadMessageW(
+            GetCurrentThreadId(),
+            WM_APP_GESTURE,
+            WPARAM(index),
+            LPARAM(0),
+        );
+    }
+}
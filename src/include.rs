@@ -0,0 +1,94 @@
+//! Configs that include other configs.
+//!
+//! Included files are merged in order, then the including config on top:
+//! objects merge key by key, other values are overridden, and lists are
+//! joined. A binding replaces an included one for the same combo.
+
+use std::{error::Error, path::Path};
+
+use serde_json::{Map, Value};
+
+/// Includes can include further files, up to this depth
+const MAX_DEPTH: usize = 8;
+
+/// Fields that together identify what a binding is bound to
+const BINDING_FIELDS: [&str; 5] = ["modifiers", "key", "button", "double_tap", "when"];
+
+/// Merge the files `config` includes into it. Paths are relative to the
+/// directory of the including file, and can use environment variables.
+pub fn resolve(path: &Path, config: Value) -> Result<Value, Box<dyn Error>> {
+    resolve_nested(path, config, 0)
+}
+
+fn resolve_nested(path: &Path, mut config: Value, depth: usize) -> Result<Value, Box<dyn Error>> {
+    let Some(includes) = config.as_object_mut().and_then(|c| c.remove("include")) else {
+        return Ok(config);
+    };
+
+    if depth == MAX_DEPTH {
+        return Err(format!(
+            "{}: includes are nested too deeply, do two files include each other?",
+            path.display()
+        )
+        .into());
+    }
+
+    let includes: Vec<String> = serde_json::from_value(includes)?;
+    let dir = path.parent().unwrap_or(Path::new("."));
+
+    let mut merged = Value::Object(Map::new());
+
+    for include in includes {
+        let include_path = dir.join(crate::expand::expand(&include));
+
+        let included = crate::load_document(&include_path)
+            .map_err(|err| format!("Failed to include {}: {err}", include_path.display()))?;
+
+        merge(
+            &mut merged,
+            resolve_nested(&include_path, included, depth + 1)?,
+        );
+    }
+
+    merge(&mut merged, config);
+
+    Ok(merged)
+}
+
+fn merge(base: &mut Value, over: Value) {
+    match (base, over) {
+        (Value::Object(base), Value::Object(over)) => {
+            for (key, value) in over {
+                match base.get_mut(&key) {
+                    Some(existing) => merge(existing, value),
+                    None => {
+                        base.insert(key, value);
+                    }
+                }
+            }
+        }
+        (Value::Array(base), Value::Array(over)) => {
+            base.retain(|item| !over.iter().any(|other| same_binding(item, other)));
+            base.extend(over);
+        }
+        (base, over) => *base = over,
+    }
+}
+
+fn same_binding(a: &Value, b: &Value) -> bool {
+    let (Some(a), Some(b)) = (a.as_object(), b.as_object()) else {
+        return false;
+    };
+
+    let is_binding = |bind: &Map<String, Value>| {
+        ["key", "button", "double_tap"]
+            .iter()
+            .any(|field| bind.contains_key(*field))
+    };
+
+    is_binding(a)
+        && is_binding(b)
+        && BINDING_FIELDS
+            .iter()
+            .all(|field| a.get(*field) == b.get(*field))
+}
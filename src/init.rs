@@ -0,0 +1,127 @@
+//! `winmgr init`, which asks a few questions on the console and writes a
+//! config tailored to the answers.
+
+use std::{
+    error::Error,
+    io::{self, BufRead, Write},
+};
+
+/// Modifier combos offered for the starter keybinds, as named in the template
+const SCHEMES: [(&str, &str); 4] = [
+    ("Windows+Alt", r#"["0x8", "0x1"]"#),
+    ("Control+Alt", r#"["0x2", "0x1"]"#),
+    ("Windows+Shift", r#"["0x8", "0x4"]"#),
+    ("Control+Alt+Shift", r#"["0x2", "0x1", "0x4"]"#),
+];
+
+pub fn run() -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let config_path = crate::profile_config_path(None)?;
+
+    println!("Setting up {}", config_path.display());
+
+    if config_path.exists() && !confirm("A config already exists there, replace it?", false)? {
+        return Ok(());
+    }
+
+    println!();
+    println!("Which modifiers should the keybinds use?");
+
+    for (index, (name, _)) in SCHEMES.iter().enumerate() {
+        println!("  {}) {name}", index + 1);
+    }
+
+    println!("  {}) None, start without keybinds", SCHEMES.len() + 1);
+
+    let choice = ask_number("Choice", 1, 1..=SCHEMES.len() as u32 + 1)? as usize;
+    let margin = ask_number("Margin around windows, in pixels", 0, 0..=u8::MAX as u32)?;
+    let gap = ask_number("Gap between windows, in pixels", 0, 0..=u8::MAX as u32)?;
+    let autostart = confirm("Start winmgr when you sign in?", true)?;
+
+    let mut template = crate::config_template(choice > SCHEMES.len())
+        .replace("margin = 0", &format!("margin = {margin}"))
+        .replace("gap = 0", &format!("gap = {gap}"));
+
+    if let Some((name, modifiers)) = SCHEMES.get(choice - 1) {
+        let (default_name, default_modifiers) = SCHEMES[0];
+
+        template = template
+            .replace(default_name, name)
+            .replace(default_modifiers, modifiers);
+    }
+
+    crate::write_config(&config_path, &template)?;
+
+    println!();
+    println!("Wrote {}", config_path.display());
+
+    if autostart {
+        crate::install_autostart()?;
+        println!("winmgr will start when you sign in");
+    }
+
+    println!("Start it now with `winmgr run`");
+
+    Ok(())
+}
+
+/// Ask a yes/no question, with the answer given on an empty line
+fn confirm(question: &str, default: bool) -> io::Result<bool> {
+    let hint = match default {
+        true => "Y/n",
+        false => "y/N",
+    };
+
+    loop {
+        match prompt(&format!("{question} [{hint}]"))?
+            .to_ascii_lowercase()
+            .as_str()
+        {
+            "" => return Ok(default),
+            "y" | "yes" => return Ok(true),
+            "n" | "no" => return Ok(false),
+            _ => println!("Please answer y or n"),
+        }
+    }
+}
+
+/// Ask for a number within `range`, with the answer given on an empty line
+fn ask_number(
+    question: &str,
+    default: u32,
+    range: std::ops::RangeInclusive<u32>,
+) -> io::Result<u32> {
+    loop {
+        let answer = prompt(&format!("{question} [{default}]"))?;
+
+        if answer.is_empty() {
+            return Ok(default);
+        }
+
+        match answer.parse() {
+            Ok(number) if range.contains(&number) => return Ok(number),
+            _ => println!(
+                "Please enter a number from {} to {}",
+                range.start(),
+                range.end()
+            ),
+        }
+    }
+}
+
+fn prompt(question: &str) -> io::Result<String> {
+    print!("{question}: ");
+    io::stdout().flush()?;
+
+    let mut line = String::new();
+
+    if io::stdin().lock().read_line(&mut line)? == 0 {
+        return Err(io::Error::new(
+            io::ErrorKind::UnexpectedEof,
+            "No answer given",
+        ));
+    }
+
+    Ok(line.trim().to_owned())
+}
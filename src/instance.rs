@@ -0,0 +1,84 @@
+//! Keeping to one running winmgr per session, since two would fight over the
+//! same hotkeys.
+//!
+//! The running instance owns a named mutex for as long as it lives. Windows
+//! releases it when the process exits, however that happens.
+
+use std::error::Error;
+
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, HANDLE, WAIT_ABANDONED, WAIT_OBJECT_0},
+        System::Threading::{
+            CreateMutexW, MUTEX_MODIFY_STATE, OpenMutexW, ReleaseMutex,
+            SYNCHRONIZATION_SYNCHRONIZE, WaitForSingleObject,
+        },
+    },
+    core::{PCWSTR, w},
+};
+
+use crate::ipc;
+
+const MUTEX_NAME: PCWSTR = w!(r"Local\winmgr");
+
+/// How long to wait for a running instance to exit when asked to
+const EXIT_TIMEOUT_MS: u32 = 5000;
+
+/// Become the running instance. If another one is running, ask it to exit
+/// when `replace` is set, otherwise fail.
+pub unsafe fn acquire(replace: bool) -> Result<(), Box<dyn Error>> {
+    let mutex = CreateMutexW(None, false, MUTEX_NAME)?;
+
+    if try_lock(mutex, 0) {
+        return Ok(());
+    }
+
+    if !replace {
+        return Err("winmgr is already running, pass --replace to take over from it".into());
+    }
+
+    ask_to_exit(mutex)
+}
+
+/// Ask the running instance to exit and wait until it has. Returns false if
+/// none was running.
+pub unsafe fn stop() -> Result<bool, Box<dyn Error>> {
+    let access = SYNCHRONIZATION_SYNCHRONIZE | MUTEX_MODIFY_STATE;
+
+    let Ok(mutex) = OpenMutexW(access, false, MUTEX_NAME) else {
+        return Ok(false);
+    };
+
+    // Nobody owns the mutex, so the instance that created it is gone
+    if try_lock(mutex, 0) {
+        let _ = ReleaseMutex(mutex);
+        let _ = CloseHandle(mutex);
+        return Ok(false);
+    }
+
+    let result = ask_to_exit(mutex);
+
+    let _ = ReleaseMutex(mutex);
+    let _ = CloseHandle(mutex);
+
+    result.map(|()| true)
+}
+
+/// Ask the instance owning `mutex` to exit, then take the mutex over
+unsafe fn ask_to_exit(mutex: HANDLE) -> Result<(), Box<dyn Error>> {
+    ipc::send(&ipc::Request::Quit)?;
+
+    match try_lock(mutex, EXIT_TIMEOUT_MS) {
+        true => Ok(()),
+        false => Err("The running winmgr didn't exit in time".into()),
+    }
+}
+
+/// Take ownership of the mutex, waiting up to `timeout_ms`. A mutex left by
+/// a process that crashed counts as free.
+unsafe fn try_lock(mutex: HANDLE, timeout_ms: u32) -> bool {
+    matches!(
+        WaitForSingleObject(mutex, timeout_ms),
+        WAIT_OBJECT_0 | WAIT_ABANDONED
+    )
+}
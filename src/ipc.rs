@@ -0,0 +1,292 @@
+//! Control of the running instance over the named pipe `\\.\pipe\winmgr`.
+//!
+//! A client writes one JSON request per line and reads one JSON response line
+//! back, e.g. `{"command": "apply-layout", "layout": "LeftHalf"}`. Requests
+//! are answered on the main thread, which owns all the state: the pipe thread
+//! posts each one to the message loop and waits for the reply.
+
+use std::{
+    error::Error,
+    fs::{File, OpenOptions},
+    io::{BufRead, BufReader, Write},
+    os::windows::io::FromRawHandle,
+    path::PathBuf,
+    sync::mpsc::{self, Sender},
+    thread,
+    time::Duration,
+};
+
+use serde::{Deserialize, Serialize};
+use windows::{
+    Win32::{
+        Foundation::{ERROR_PIPE_CONNECTED, LPARAM, WPARAM},
+        Storage::FileSystem::PIPE_ACCESS_DUPLEX,
+        System::{
+            Pipes::{
+                ConnectNamedPipe, CreateNamedPipeW, PIPE_READMODE_BYTE, PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_TYPE_BYTE, PIPE_UNLIMITED_INSTANCES, PIPE_WAIT,
+            },
+            Threading::GetCurrentThreadId,
+        },
+        UI::WindowsAndMessaging::{PostThreadMessageW, WM_APP},
+    },
+    core::HSTRING,
+};
+
+use crate::{Layout, WindowMatch};
+
+pub const PIPE_NAME: &str = r"\\.\pipe\winmgr";
+
+/// Posted to the message loop with a `Pending` request in `lParam`
+pub const WM_APP_IPC: u32 = WM_APP + 5;
+
+/// How long a client waits on the main thread before giving up
+const REPLY_TIMEOUT: Duration = Duration::from_secs(5);
+
+const BUFFER_SIZE: u32 = 4096;
+
+#[derive(Debug, Serialize, Deserialize)]
+#[serde(tag = "command", rename_all = "kebab-case")]
+pub enum Request {
+    /// Lay out a window: the one with the handle `hwnd`, the topmost one
+    /// matching `window`, or else the focused one
+    ApplyLayout {
+        layout: Layout,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        hwnd: Option<isize>,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        window: Option<WindowMatch>,
+    },
+    Reload,
+    Pause,
+    Resume,
+    /// Switch to the config of a named profile, or back to the main config
+    /// with `default`
+    SwitchProfile {
+        name: String,
+    },
+    /// Switch the keybinds with this `name` off, or back on, or to `enabled`
+    /// if given
+    ToggleKeybinds {
+        name: String,
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        enabled: Option<bool>,
+    },
+    QueryState,
+    QueryKeys,
+    Quit,
+}
+
+#[derive(Debug, Default, Serialize, Deserialize)]
+pub struct Response {
+    pub ok: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub state: Option<State>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub keys: Option<Vec<KeyInfo>>,
+}
+
+impl Response {
+    pub fn ok() -> Self {
+        Response {
+            ok: true,
+            ..Default::default()
+        }
+    }
+
+    pub fn error(message: impl Into<String>) -> Self {
+        Response {
+            ok: false,
+            error: Some(message.into()),
+            ..Default::default()
+        }
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct State {
+    pub config: PathBuf,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub profile: Option<String>,
+    pub paused: bool,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    /// Names of keybinds switched off, sorted
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    pub disabled: Vec<String>,
+    /// Keybinds in effect, those of the active layer included
+    pub keybinds: usize,
+    /// Hotkeys registered with Windows. Keybinds for the same combo share one.
+    pub registered: usize,
+    /// Keybinds that failed to register
+    pub failed: usize,
+    pub uptime_secs: u64,
+    /// Latest actions performed, oldest first
+    pub recent_actions: Vec<RecentAction>,
+}
+
+#[derive(Debug, Serialize, Deserialize)]
+pub struct RecentAction {
+    /// The action as it would be written in the config
+    pub action: String,
+    pub secs_ago: u64,
+}
+
+/// A keybind and whether it got its combo, for `winmgr keys`
+#[derive(Debug, Serialize, Deserialize)]
+pub struct KeyInfo {
+    /// Combo such as `Win+Shift+Left`
+    pub combo: String,
+    /// Layer the keybind belongs to, if not the base keybinds
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub layer: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub name: Option<String>,
+    /// The action as it would be written in the config, or a summary of a
+    /// cycle or chord
+    pub action: String,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub when: Option<WindowMatch>,
+    pub status: KeyStatus,
+    /// Why the combo couldn't be registered
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    pub error: Option<String>,
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[serde(rename_all = "snake_case")]
+pub enum KeyStatus {
+    /// Registered by the running winmgr
+    Registered,
+    /// Not registered: the combo is invalid or Windows refused it
+    Failed,
+    /// Switched off at runtime
+    Disabled,
+    /// Released while winmgr is paused
+    Paused,
+    /// Read from the config: no other program holds the combo
+    Free,
+    /// Read from the config: another program holds the combo
+    Taken,
+}
+
+/// A request waiting on the main thread, with the way back to its client
+pub struct Pending {
+    pub request: Request,
+    reply: Sender<Response>,
+}
+
+impl Pending {
+    pub fn reply(self, response: Response) {
+        let _ = self.reply.send(response);
+    }
+}
+
+/// Take back a request posted with `WM_APP_IPC`
+///
+/// # Safety
+///
+/// `lparam` must come from a `WM_APP_IPC` message, and each message may only
+/// be taken once.
+pub unsafe fn take(lparam: LPARAM) -> Pending {
+    *Box::from_raw(lparam.0 as *mut Pending)
+}
+
+/// Serve the pipe on a background thread, posting requests to the calling
+/// thread as `WM_APP_IPC`
+pub unsafe fn serve() {
+    let thread_id = GetCurrentThreadId();
+
+    thread::spawn(move || unsafe {
+        loop {
+            let pipe = CreateNamedPipeW(
+                &HSTRING::from(PIPE_NAME),
+                PIPE_ACCESS_DUPLEX,
+                PIPE_TYPE_BYTE | PIPE_READMODE_BYTE | PIPE_WAIT | PIPE_REJECT_REMOTE_CLIENTS,
+                PIPE_UNLIMITED_INSTANCES,
+                BUFFER_SIZE,
+                BUFFER_SIZE,
+                0,
+                None,
+            );
+
+            if pipe.is_invalid() {
+                eprintln!("Failed to create pipe {PIPE_NAME}");
+                return;
+            }
+
+            // Closes the pipe instance once the client is served
+            let file = File::from_raw_handle(pipe.0);
+
+            // The client may connect between creating the pipe and waiting for it
+            if let Err(err) = ConnectNamedPipe(pipe, None)
+                && err.code() != ERROR_PIPE_CONNECTED.to_hresult()
+            {
+                eprintln!("Failed to accept pipe client: {err}");
+                continue;
+            }
+
+            if let Err(err) = handle_client(file, thread_id) {
+                eprintln!("Failed to serve pipe client: {err}");
+            }
+        }
+    });
+}
+
+unsafe fn handle_client(file: File, thread_id: u32) -> Result<(), Box<dyn Error>> {
+    let mut writer = file.try_clone()?;
+    let reader = BufReader::new(file);
+
+    for line in reader.lines() {
+        let line = line?;
+
+        if line.trim().is_empty() {
+            continue;
+        }
+
+        let response = match serde_json::from_str(&line) {
+            Ok(request) => forward(request, thread_id),
+            Err(err) => Response::error(format!("Invalid request: {err}")),
+        };
+
+        serde_json::to_writer(&mut writer, &response)?;
+        writer.write_all(b"\n")?;
+        writer.flush()?;
+    }
+
+    Ok(())
+}
+
+unsafe fn forward(request: Request, thread_id: u32) -> Response {
+    let (reply, response) = mpsc::channel();
+    let pending = Box::into_raw(Box::new(Pending { request, reply }));
+
+    if PostThreadMessageW(thread_id, WM_APP_IPC, WPARAM(0), LPARAM(pending as isize)).is_err() {
+        drop(Box::from_raw(pending));
+        return Response::error("winmgr is shutting down");
+    }
+
+    response
+        .recv_timeout(REPLY_TIMEOUT)
+        .unwrap_or_else(|_| Response::error("winmgr didn't answer in time"))
+}
+
+/// Send a request to the running instance and wait for its response
+pub fn send(request: &Request) -> Result<Response, Box<dyn Error>> {
+    let mut pipe = OpenOptions::new()
+        .read(true)
+        .write(true)
+        .open(PIPE_NAME)
+        .map_err(|err| format!("winmgr doesn't seem to be running: {err}"))?;
+
+    serde_json::to_writer(&mut pipe, request)?;
+    pipe.write_all(b"\n")?;
+    pipe.flush()?;
+
+    let mut line = String::new();
+    BufReader::new(pipe).read_line(&mut line)?;
+
+    Ok(serde_json::from_str(&line)?)
+}
@@ -0,0 +1,200 @@
+//! JSON with comments and trailing commas.
+//!
+//! Comments and trailing commas are blanked out rather than removed, so that
+//! line and column numbers in parse errors still point into the original file.
+
+use std::{error::Error, path::Path};
+
+use serde::de::DeserializeOwned;
+
+pub fn from_str<T: DeserializeOwned>(path: &Path, text: &str) -> Result<T, Box<dyn Error>> {
+    serde_json::from_str(&strip(text)).map_err(|err| describe(path, text, &err).into())
+}
+
+/// Replace comments and trailing commas with spaces, keeping line breaks
+fn strip(text: &str) -> String {
+    let without_comments = strip_comments(text);
+
+    let bytes = without_comments.as_bytes();
+    let mut out = without_comments.clone().into_bytes();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    for (i, &byte) in bytes.iter().enumerate() {
+        if in_string {
+            match byte {
+                _ if escaped => escaped = false,
+                b'\\' => escaped = true,
+                b'"' => in_string = false,
+                _ => {}
+            }
+            continue;
+        }
+
+        match byte {
+            b'"' => in_string = true,
+            b',' => {
+                let next = bytes[i + 1..].iter().find(|b| !b.is_ascii_whitespace());
+
+                if matches!(next, Some(b'}' | b']')) {
+                    out[i] = b' ';
+                }
+            }
+            _ => {}
+        }
+    }
+
+    // Only ASCII commas were replaced, so the bytes are still valid UTF-8
+    String::from_utf8(out).unwrap_or(without_comments)
+}
+
+fn strip_comments(text: &str) -> String {
+    let mut out = String::with_capacity(text.len());
+    let mut chars = text.chars().peekable();
+    let mut in_string = false;
+    let mut escaped = false;
+
+    let blank = |out: &mut String, c: char| match c {
+        '\n' | '\r' => out.push(c),
+        _ => out.extend(std::iter::repeat_n(' ', c.len_utf8())),
+    };
+
+    while let Some(c) = chars.next() {
+        if in_string {
+            match c {
+                _ if escaped => escaped = false,
+                '\\' => escaped = true,
+                '"' => in_string = false,
+                _ => {}
+            }
+            out.push(c);
+            continue;
+        }
+
+        match (c, chars.peek()) {
+            ('"', _) => {
+                in_string = true;
+                out.push(c);
+            }
+            ('/', Some('/')) => {
+                blank(&mut out, c);
+
+                while let Some(&next) = chars.peek() {
+                    if next == '\n' {
+                        break;
+                    }
+
+                    blank(&mut out, next);
+                    chars.next();
+                }
+            }
+            ('/', Some('*')) => {
+                blank(&mut out, c);
+                blank(&mut out, chars.next().unwrap_or('*'));
+
+                let mut star = false;
+
+                for next in chars.by_ref() {
+                    blank(&mut out, next);
+
+                    if star && next == '/' {
+                        break;
+                    }
+
+                    star = next == '*';
+                }
+            }
+            _ => out.push(c),
+        }
+    }
+
+    out
+}
+
+/// The error with the offending line of the file and a marker under the column
+fn describe(path: &Path, text: &str, err: &serde_json::Error) -> String {
+    let mut message = format!("{}: {err}", path.display());
+
+    if let Some(line) = err.line().checked_sub(1).and_then(|n| text.lines().nth(n)) {
+        let number = err.line().to_string();
+        let indent = " ".repeat(number.len());
+        // serde_json counts columns in bytes, the marker goes under characters
+        let column = err.column().saturating_sub(1);
+        let column = line
+            .get(..column)
+            .map_or(column, |before| before.chars().count());
+
+        message.push_str(&format!(
+            "\n\n{number} | {}\n{indent} | {}^",
+            line.trim_end(),
+            " ".repeat(column),
+        ));
+    }
+
+    message
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn keeps_comment_markers_inside_strings() {
+        let text = r#"{ "url": "http://example.com/*path*/" }"#;
+
+        assert_eq!(strip(text), text);
+    }
+
+    #[test]
+    fn keeps_escaped_quotes_inside_strings() {
+        let text = r#"{ "title": "say \"hi\" // not a comment", }"#;
+
+        assert_eq!(
+            strip(text),
+            r#"{ "title": "say \"hi\" // not a comment"  }"#
+        );
+    }
+
+    #[test]
+    fn blanks_line_comments() {
+        assert_eq!(strip("{} // comment\n"), "{}           \n");
+    }
+
+    #[test]
+    fn blanks_block_comments_across_lines() {
+        assert_eq!(strip("{ /* one\ntwo */ }"), "{       \n       }");
+    }
+
+    #[test]
+    fn blanks_trailing_comma_before_comment() {
+        let text = "{ \"a\": 1, // last\n}";
+
+        assert_eq!(strip(text), "{ \"a\": 1         \n}");
+        assert_eq!(
+            from_str::<serde_json::Value>(Path::new("config.json"), text).unwrap(),
+            serde_json::json!({ "a": 1 })
+        );
+    }
+
+    #[test]
+    fn keeps_byte_offsets_of_multibyte_comments() {
+        assert_eq!(strip("// é\n{}"), "     \n{}");
+    }
+
+    #[test]
+    fn marks_column_after_multibyte_characters() {
+        let text = r#"{ "name": "café" x }"#;
+        let err = serde_json::from_str::<serde_json::Value>(text).unwrap_err();
+        let message = describe(Path::new("config.json"), text, &err);
+
+        let lines: Vec<&str> = message.lines().collect();
+        let [.., source, marker] = lines.as_slice() else {
+            panic!("no marker in {message}");
+        };
+
+        assert_eq!(
+            marker.chars().position(|c| c == '^'),
+            source.chars().position(|c| c == 'x')
+        );
+    }
+}
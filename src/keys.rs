@@ -0,0 +1,135 @@
+//! `winmgr keys`, the keybind table as winmgr resolves it: each combo, what it
+//! does and whether it got registered.
+//!
+//! The running winmgr reports on the keybinds it has registered. When it isn't
+//! running, or another config is asked for, each combo is registered for a
+//! moment and released again, which tells whether another program holds it.
+
+use std::{error::Error, path::PathBuf};
+
+use windows::Win32::UI::Input::KeyboardAndMouse::{MOD_NOREPEAT, RegisterHotKey, UnregisterHotKey};
+
+use crate::{
+    Config, KeyBind, WindowMatch,
+    ipc::{self, KeyInfo, KeyStatus},
+    list,
+};
+
+/// Hotkey id for trying out combos, clear of those the running winmgr uses
+const TRIAL_HOTKEY_ID: i32 = 0x3000;
+
+/// Longest action shown in the table, in characters
+const ACTION_WIDTH: usize = 50;
+
+pub fn run(
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    json: bool,
+) -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let live = match config_path.is_none() && profile.is_none() {
+        true => ipc::send(&ipc::Request::QueryKeys).ok(),
+        false => None,
+    };
+
+    let keys = match live {
+        Some(response) => response.keys.ok_or("winmgr didn't report its keybinds")?,
+        None => {
+            let config_path = match config_path {
+                Some(path) => path,
+                None => crate::profile_config_path(profile.as_deref())?,
+            };
+
+            unsafe { from_config(&crate::get_config(&config_path)?) }
+        }
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&keys)?);
+        return Ok(());
+    }
+
+    let table: Vec<[String; 5]> = keys
+        .into_iter()
+        .map(|key| {
+            let status = match key.error {
+                Some(err) => format!("{} ({err})", describe_status(key.status)),
+                None => describe_status(key.status).to_owned(),
+            };
+
+            [
+                key.combo,
+                key.layer.unwrap_or_default(),
+                list::truncate(&key.action, ACTION_WIDTH),
+                key.when.as_ref().map(describe_when).unwrap_or_default(),
+                status,
+            ]
+        })
+        .collect();
+
+    list::print_table(["COMBO", "LAYER", "ACTION", "WHEN", "STATUS"], &table);
+
+    Ok(())
+}
+
+/// Every keybind in the config, those of each layer after the base ones
+unsafe fn from_config(config: &Config) -> Vec<KeyInfo> {
+    let layers = config
+        .layers
+        .iter()
+        .flat_map(|(layer, keybinds)| keybinds.iter().map(move |k| (Some(layer.as_str()), k)));
+
+    config
+        .keybinds
+        .iter()
+        .map(|keybind| (None, keybind))
+        .chain(layers)
+        .map(|(layer, keybind)| {
+            let (status, error) = try_combo(keybind);
+            keybind.info(layer, status, error)
+        })
+        .collect()
+}
+
+/// Register the keybind's combo and release it straight away
+unsafe fn try_combo(keybind: &KeyBind) -> (KeyStatus, Option<String>) {
+    let (mods, key) = match keybind.parse_combo() {
+        Ok(combo) => combo,
+        Err(err) => return (KeyStatus::Failed, Some(err)),
+    };
+
+    match RegisterHotKey(None, TRIAL_HOTKEY_ID, mods | MOD_NOREPEAT, key.0.into()) {
+        Ok(()) => {
+            let _ = UnregisterHotKey(None, TRIAL_HOTKEY_ID);
+            (KeyStatus::Free, None)
+        }
+        Err(err) => (KeyStatus::Taken, Some(err.to_string())),
+    }
+}
+
+fn describe_status(status: KeyStatus) -> &'static str {
+    match status {
+        KeyStatus::Registered => "registered",
+        KeyStatus::Failed => "failed",
+        KeyStatus::Disabled => "disabled",
+        KeyStatus::Paused => "paused",
+        KeyStatus::Free => "free",
+        KeyStatus::Taken => "taken",
+    }
+}
+
+/// Short form of a `when` clause, e.g. `exe=Code.exe title=README`
+fn describe_when(when: &WindowMatch) -> String {
+    let parts = [
+        ("exe", &when.exe),
+        ("class", &when.class),
+        ("title", &when.title),
+    ];
+
+    parts
+        .iter()
+        .filter_map(|(field, value)| Some(format!("{field}={}", value.as_deref()?)))
+        .collect::<Vec<_>>()
+        .join(" ")
+}
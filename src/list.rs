@@ -0,0 +1,219 @@
+//! `winmgr list-windows` and `list-monitors`, showing what winmgr sees when
+//! matching windows and placing layouts.
+
+use std::{collections::HashMap, error::Error};
+
+use serde::Serialize;
+use windows::Win32::{
+    Foundation::HWND,
+    Graphics::Gdi::MONITORINFO,
+    UI::WindowsAndMessaging::{IsIconic, MONITORINFOF_PRIMARY},
+};
+
+use crate::{corners, display, window};
+
+/// Longest title shown in the table, in characters
+const TITLE_WIDTH: usize = 40;
+
+#[derive(Serialize)]
+struct WindowRow {
+    hwnd: String,
+    title: String,
+    class: String,
+    exe: Option<String>,
+    /// Window as `[left, top, right, bottom]`
+    rect: [i32; 4],
+    /// Number of the monitor the window is mostly on, as used by `monitor`
+    monitor: Option<u32>,
+    minimized: bool,
+}
+
+impl WindowRow {
+    unsafe fn new(hwnd: HWND) -> Self {
+        let rect = window::rect(hwnd).unwrap_or_default();
+
+        WindowRow {
+            hwnd: format!("{:#x}", hwnd.0 as usize),
+            title: window::title(hwnd),
+            class: window::class_name(hwnd),
+            exe: window::process_name(hwnd),
+            rect: corners(rect),
+            monitor: crate::monitor_info(hwnd).and_then(|mi| crate::monitor_number(&mi)),
+            minimized: IsIconic(hwnd).as_bool(),
+        }
+    }
+}
+
+pub fn windows(all: bool, json: bool) -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let rows: Vec<WindowRow> = unsafe {
+        let windows = match all {
+            true => window::visible_windows(),
+            false => window::app_windows(),
+        };
+
+        windows
+            .into_iter()
+            .map(|hwnd| WindowRow::new(hwnd))
+            .collect()
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&rows)?);
+        return Ok(());
+    }
+
+    let table: Vec<[String; 6]> = rows
+        .into_iter()
+        .map(|row| {
+            let state = if row.minimized { " (minimized)" } else { "" };
+
+            [
+                row.hwnd,
+                truncate(&row.title, TITLE_WIDTH),
+                row.class,
+                row.exe.unwrap_or_default(),
+                format!("{}{state}", describe_rect(row.rect)),
+                row.monitor.map(|n| n.to_string()).unwrap_or_default(),
+            ]
+        })
+        .collect();
+
+    print_table(["HWND", "TITLE", "CLASS", "EXE", "RECT", "MONITOR"], &table);
+
+    Ok(())
+}
+
+#[derive(Serialize)]
+struct MonitorRow {
+    /// Number of the monitor as used by `monitor` and `monitors`
+    index: u32,
+    device: Option<String>,
+    /// Friendly name from the monitor's EDID, e.g. `DELL U2720Q`
+    name: Option<String>,
+    /// Whole monitor as `[left, top, right, bottom]`
+    rect: [i32; 4],
+    /// Monitor less the taskbar and docked toolbars
+    work_area: [i32; 4],
+    dpi: u32,
+    primary: bool,
+}
+
+impl MonitorRow {
+    unsafe fn new(index: u32, mi: &MONITORINFO, names: &HashMap<String, String>) -> Self {
+        let device = crate::monitor_device_name(mi);
+
+        MonitorRow {
+            index,
+            name: device
+                .as_ref()
+                .and_then(|device| names.get(device).cloned()),
+            device,
+            rect: corners(mi.rcMonitor),
+            work_area: corners(mi.rcWork),
+            dpi: crate::monitor_dpi(mi),
+            primary: mi.dwFlags & MONITORINFOF_PRIMARY != 0,
+        }
+    }
+}
+
+pub fn monitors(json: bool) -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let rows: Vec<MonitorRow> = unsafe {
+        let names = display::friendly_names();
+
+        crate::monitor_infos()
+            .iter()
+            .enumerate()
+            .map(|(index, mi)| MonitorRow::new(index as u32 + 1, mi, &names))
+            .collect()
+    };
+
+    let screen = unsafe { crate::virtual_screen() };
+
+    if json {
+        // The rect of all monitors together, as the text footer shows it
+        let report = serde_json::json!({ "monitors": rows, "all_monitors": corners(screen) });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    let table: Vec<[String; 7]> = rows
+        .into_iter()
+        .map(|row| {
+            [
+                row.index.to_string(),
+                row.device.unwrap_or_default(),
+                row.name.unwrap_or_default(),
+                describe_rect(row.rect),
+                describe_rect(row.work_area),
+                format!("{} ({}%)", row.dpi, row.dpi * 100 / 96),
+                if row.primary { "yes" } else { "" }.to_owned(),
+            ]
+        })
+        .collect();
+
+    print_table(
+        [
+            "INDEX",
+            "DEVICE",
+            "NAME",
+            "RECT",
+            "WORK AREA",
+            "DPI",
+            "PRIMARY",
+        ],
+        &table,
+    );
+
+    // What custom layouts in screen coordinates need, which the table leaves
+    // to be worked out on rigs where monitors sit left of or above the primary
+    println!();
+    println!(
+        "All monitors: {}",
+        describe_rect([screen.left, screen.top, screen.right, screen.bottom])
+    );
+    println!("Coordinates are physical pixels from the primary monitor's top-left corner,");
+    println!("so monitors to its left or above it have negative ones, whatever their scaling.");
+
+    Ok(())
+}
+
+fn describe_rect([left, top, right, bottom]: [i32; 4]) -> String {
+    format!("{left},{top} {}x{}", right - left, bottom - top)
+}
+
+pub fn print_table<const N: usize>(headers: [&str; N], rows: &[[String; N]]) {
+    let mut widths = headers.map(|header| header.chars().count());
+
+    for row in rows {
+        for (width, cell) in widths.iter_mut().zip(row) {
+            *width = (*width).max(cell.chars().count());
+        }
+    }
+
+    let print_row = |cells: [&str; N]| {
+        let line: Vec<String> = cells
+            .iter()
+            .zip(widths)
+            .map(|(cell, width)| format!("{cell:width$}"))
+            .collect();
+
+        println!("{}", line.join("  ").trim_end());
+    };
+
+    print_row(headers);
+
+    for row in rows {
+        print_row(row.each_ref().map(String::as_str));
+    }
+}
+
+pub fn truncate(text: &str, max: usize) -> String {
+    match text.chars().count() > max {
+        true => format!("{}…", text.chars().take(max - 1).collect::<String>()),
+        false => text.to_owned(),
+    }
+}
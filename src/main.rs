@@ -1,28 +1,96 @@
 #![allow(unsafe_op_in_unsafe_fn)]
 #![cfg_attr(not(debug_assertions), windows_subsystem = "windows")]
 
-use std::{env, error::Error, fmt::Write, fs::File, io};
+mod desktop;
+mod display;
+mod expand;
+mod hooks;
+mod include;
+mod init;
+mod instance;
+mod ipc;
+mod jsonc;
+mod keys;
+mod list;
+mod migrate;
+mod portable;
+mod report;
+mod snapshot;
+mod switcher;
+mod system;
+mod validate;
+mod watch;
+mod window;
 
-use clap::{Parser, Subcommand};
-use directories::UserDirs;
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap, HashSet, VecDeque, hash_map::DefaultHasher},
+    env,
+    error::Error,
+    fmt::Write,
+    fs::{self, File},
+    hash::{Hash, Hasher},
+    io, iter, mem,
+    path::{Path, PathBuf},
+    process,
+    thread::{self, JoinHandle},
+    time::{Instant, SystemTime},
+};
+
+use clap::{ArgGroup, Parser, Subcommand};
+use directories::BaseDirs;
 use nohash_hasher::{BuildNoHashHasher, IntMap};
-use serde::{Deserialize, Serialize};
+use regex::{Regex, RegexBuilder};
+use schemars::JsonSchema;
+use serde::{Deserialize, Serialize, de::DeserializeOwned};
+use window::Direction;
 use windows::{
     Win32::{
-        Foundation::HWND,
+        Foundation::{
+            COLORREF, CloseHandle, HANDLE, HWND, LPARAM, POINT, RECT, WAIT_OBJECT_0, WPARAM,
+        },
         Graphics::Gdi::{
-            GetMonitorInfoW, MONITOR_DEFAULTTONEAREST, MONITORINFO, MonitorFromWindow,
+            ClientToScreen, EnumDisplayMonitors, GetMonitorInfoW, HDC, HMONITOR,
+            MONITOR_DEFAULTTONEAREST, MONITOR_DEFAULTTOPRIMARY, MONITORINFO, MONITORINFOEXW,
+            MonitorFromPoint, MonitorFromRect, MonitorFromWindow,
+        },
+        System::{
+            Console::{ATTACH_PARENT_PROCESS, AttachConsole},
+            DataExchange::{GlobalAddAtomA, GlobalDeleteAtom},
+            Threading::{
+                GetCurrentProcessId, INFINITE, OpenProcess, PROCESS_SYNCHRONIZE, PROCESS_TERMINATE,
+                TerminateProcess, WaitForSingleObject,
+            },
         },
-        System::DataExchange::GlobalAddAtomA,
         UI::{
+            HiDpi::{
+                DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2, GetDpiForMonitor, GetDpiForWindow,
+                MDT_EFFECTIVE_DPI, SetProcessDpiAwarenessContext,
+            },
             Input::KeyboardAndMouse::*,
+            Shell::{
+                ABM_GETSTATE, ABM_SETSTATE, ABS_AUTOHIDE, APPBARDATA, SEE_MASK_NOCLOSEPROCESS,
+                SHAppBarMessage, SHELLEXECUTEINFOW, ShellExecuteExW,
+            },
             WindowsAndMessaging::{
-                GetForegroundWindow, GetMessageW, MSG, SET_WINDOW_POS_FLAGS, SWP_FRAMECHANGED,
-                SWP_NOACTIVATE, SWP_NOZORDER, SetWindowPos, WM_HOTKEY, WM_QUIT,
+                DispatchMessageW, FindWindowW, GA_ROOT, GWL_EXSTYLE, GWL_STYLE, GetAncestor,
+                GetCursorPos, GetForegroundWindow, GetLayeredWindowAttributes, GetMessageW,
+                GetSystemMetrics, GetWindowLongPtrW, GetWindowRect, HWND_NOTOPMOST, HWND_TOP,
+                HWND_TOPMOST, IsIconic, IsWindow, IsWindowVisible, IsZoomed, KillTimer,
+                LAYERED_WINDOW_ATTRIBUTES_FLAGS, LWA_ALPHA, MSG, PostMessageW, PostQuitMessage,
+                SET_WINDOW_POS_FLAGS, SM_CXVIRTUALSCREEN, SM_CYCAPTION, SM_CYFRAME,
+                SM_CYVIRTUALSCREEN, SM_XVIRTUALSCREEN, SM_YVIRTUALSCREEN, SMTO_ABORTIFHUNG,
+                SW_HIDE, SW_MAXIMIZE, SW_MINIMIZE, SW_RESTORE, SW_SHOW, SW_SHOWMINNOACTIVE,
+                SW_SHOWNORMAL, SWP_FRAMECHANGED, SWP_NOACTIVATE, SWP_NOMOVE, SWP_NOSIZE,
+                SWP_NOZORDER, SendMessageTimeoutW, SetCursorPos, SetForegroundWindow,
+                SetLayeredWindowAttributes, SetTimer, SetWindowLongPtrW, SetWindowPos, ShowWindow,
+                TranslateMessage, WINDOW_EX_STYLE, WINDOW_STYLE, WM_CLOSE, WM_HOTKEY, WM_NULL,
+                WM_QUIT, WM_TIMER, WS_CAPTION, WS_EX_LAYERED, WS_EX_TOPMOST, WS_THICKFRAME,
+                WindowFromPoint,
             },
         },
     },
-    core::PCSTR,
+    core::{BOOL, HSTRING, PCSTR, PCWSTR, w},
 };
 use winreg::{
     RegKey,
@@ -32,12 +100,91 @@ use winreg::{
 const RUN_KEY_PATH: &str = r"Software\Microsoft\Windows\CurrentVersion\Run";
 const RUN_VALUE_NAME: &str = "WinMgr";
 
+/// Hotkey ids for chord follow-up keys, kept below the atom range used by keybinds
+const CHORD_HOTKEY_BASE: i32 = 0x1000;
+const CHORD_CANCEL_HOTKEY: i32 = 0x0FFF;
+
+/// Hotkey ids for the keys that are active while in resize mode
+const RESIZE_HOTKEY_BASE: i32 = 0x2000;
+
+/// Keys that resize the window in resize mode, as (key, width steps, height steps)
+const RESIZE_KEYS: [(VIRTUAL_KEY, i32, i32); 4] = [
+    (VK_LEFT, -1, 0),
+    (VK_RIGHT, 1, 0),
+    (VK_UP, 0, -1),
+    (VK_DOWN, 0, 1),
+];
+
+/// Keys that leave resize mode
+const RESIZE_EXIT_KEYS: [VIRTUAL_KEY; 2] = [VK_ESCAPE, VK_RETURN];
+
+/// Offset between successive windows when cascading
+const CASCADE_STEP: i32 = 32;
+
+/// Percent of opacity added or removed by `OpacityUp` and `OpacityDown`
+const OPACITY_STEP: u8 = 10;
+
+/// Least opacity winmgr sets, in percent, so that a window can't vanish
+const MIN_OPACITY: u8 = 10;
+
+/// How long a desktop switch takes to go through before a window sent along
+/// is shown there
+const DESKTOP_SWITCH_MS: u32 = 250;
+
+/// How long `ForceClose` waits for a window to close before offering to end
+/// its process
+const FORCE_CLOSE_MS: u32 = 3000;
+
+/// How long the taskbar takes to give up or take back its space after
+/// auto-hide is toggled
+const TASKBAR_SETTLE_MS: u32 = 500;
+
+/// Longest wait for a window moved to a monitor with another DPI to handle
+/// the change
+const DPI_CHANGE_TIMEOUT_MS: u32 = 200;
+
+/// Most pixels any edge of a window may be off a layout's for
+/// `MirrorToMonitor` to take it as filling that layout
+const SNAP_TOLERANCE: i32 = 24;
+
+/// How often windows' places are noted for `restore_on_reconnect`
+const PLACES_POLL_MS: u32 = 2000;
+
+/// How long after monitors last changed windows are restored, since Windows
+/// moves them about for a while
+const DISPLAY_SETTLE_MS: u32 = 1500;
+
+/// Moves remembered per window for `Undo`
+const UNDO_DEPTH: usize = 10;
+
+/// Written on first run, with comments explaining the settings
+const DEFAULT_TOML_CONFIG: &str = include_str!("default_config.toml");
+const STARTER_KEYBINDS: &str = include_str!("starter_keybinds.toml");
+const MINIMAL_KEYBINDS: &str = include_str!("minimal_keybinds.toml");
+
+/// Quiet time after a change to the config file before it's reloaded, so that
+/// a save made of several writes is read once it's complete
+const CONFIG_SETTLE_MS: u32 = 250;
+
+/// Actions remembered for `winmgr status`
+const RECENT_ACTIONS: usize = 10;
+
+/// Exit code when the running winmgr turned down a request
+const EXIT_REFUSED: i32 = 1;
+
+/// Exit code when there is no running winmgr to talk to
+const EXIT_NOT_RUNNING: i32 = 3;
+
 #[derive(Parser)]
 #[command(name = "winmgr")]
 #[command(about = "Super basic window manager for Windows")]
 struct Args {
     #[command(subcommand)]
     command: Option<Command>,
+
+    /// Keep the config and log next to the executable instead of in the user profile
+    #[arg(long, global = true)]
+    portable: bool,
 }
 
 #[derive(Subcommand)]
@@ -48,13 +195,215 @@ enum Command {
     /// Uninstall startup entry for WinMgr
     Uninstall,
 
+    /// Set up a config by answering a few questions
+    Init,
+
+    /// Print a JSON Schema of the config, for editor validation and completion
+    Schema,
+
+    /// Check the config for mistakes without running
+    Validate {
+        /// Config file to check instead of the default one
+        path: Option<PathBuf>,
+
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
     /// Run WinMgr
-    Run,
+    Run {
+        /// Config file to use instead of the default one
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Use the config of a named profile, `config.<profile>.json`
+        #[arg(long, conflicts_with = "config")]
+        profile: Option<String>,
+
+        /// Create a missing config without the starter keybinds
+        #[arg(long)]
+        minimal: bool,
+
+        /// Ask an already running winmgr to exit and take over from it
+        #[arg(long)]
+        replace: bool,
+    },
+
+    /// Manage the config file
+    Config {
+        #[command(subcommand)]
+        command: ConfigCommand,
+    },
+
+    /// Lay out the focused window with the running winmgr
+    Apply {
+        /// Name of a layout, e.g. `LeftHalf`, or a layout as JSON
+        layout: String,
+    },
+
+    /// Lay out a window picked by its title, process, class or handle with
+    /// the running winmgr
+    #[command(group(ArgGroup::new("window").required(true).multiple(true)))]
+    Move {
+        /// Name of a layout, e.g. `LeftHalf`, or a layout as JSON
+        #[arg(long)]
+        layout: String,
+
+        /// Regex matched against window titles, case-insensitive
+        #[arg(long, group = "window")]
+        title: Option<String>,
+
+        /// Image name of the window's process, e.g. `Spotify.exe`
+        #[arg(long, group = "window")]
+        exe: Option<String>,
+
+        /// Window class name
+        #[arg(long, group = "window")]
+        class: Option<String>,
+
+        /// Window handle as shown by `list-windows`, e.g. `0x1a2b3c`
+        #[arg(long, group = "window", conflicts_with_all = ["title", "exe", "class"])]
+        hwnd: Option<String>,
+    },
+
+    /// Have the running winmgr reload its config
+    Reload,
+
+    /// Pause the running winmgr, releasing its keybinds
+    Pause,
+
+    /// Resume the running winmgr after `pause`
+    Resume,
+
+    /// Switch the running winmgr to the config of a named profile,
+    /// `config.<profile>.json`, or back to the main config with `default`
+    Profile { name: String },
+
+    /// Switch the running winmgr's keybinds with a `name` off, or back on
+    ToggleKeybinds {
+        name: String,
+
+        /// Switch them on, whether or not they are off
+        #[arg(long, conflicts_with = "off")]
+        on: bool,
+
+        /// Switch them off, whether or not they are on
+        #[arg(long)]
+        off: bool,
+    },
+
+    /// Have the running winmgr exit
+    Quit,
+
+    /// Have the running winmgr exit and wait until it has, e.g. before
+    /// upgrading or uninstalling. Succeeds if winmgr wasn't running.
+    Stop,
+
+    /// Show what the running winmgr is doing
+    Status {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// Save where the open windows are, or put them back
+    Snapshot {
+        #[command(subcommand)]
+        command: SnapshotCommand,
+    },
+
+    /// List open windows, to help write `when` clauses and exclusions
+    ListWindows {
+        /// Include every visible top-level window, not just those Alt+Tab shows
+        #[arg(long)]
+        all: bool,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List monitors, to help write per-monitor settings and layouts
+    ListMonitors {
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+
+    /// List keybinds with what they do and whether they got their combo, from
+    /// the running winmgr or else from the config
+    Keys {
+        /// Config file to read instead of asking the running winmgr
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Read the config of a named profile, `config.<profile>.json`
+        #[arg(long, conflicts_with = "config")]
+        profile: Option<String>,
+
+        /// Print JSON instead of a table
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum SnapshotCommand {
+    /// Save the position of every open window under a name
+    Save { name: String },
+
+    /// Move the open windows back to where a snapshot saw them
+    Restore { name: String },
+
+    /// List saved snapshots
+    List {
+        /// Print JSON instead of text
+        #[arg(long)]
+        json: bool,
+    },
+}
+
+#[derive(Subcommand)]
+enum ConfigCommand {
+    /// Open the config in the editor Windows associates with it, or
+    /// `$EDITOR` if set, then check it and reload the running winmgr
+    Edit {
+        /// Config file to edit instead of the default one
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Edit the config of a named profile, `config.<profile>.json`
+        #[arg(long, conflicts_with = "config")]
+        profile: Option<String>,
+    },
+
+    /// Upgrade a config written for an older version of winmgr in place,
+    /// keeping the original as a backup
+    Migrate {
+        /// Config file to upgrade instead of the default one
+        #[arg(long)]
+        config: Option<PathBuf>,
+
+        /// Upgrade the config of a named profile, `config.<profile>.json`
+        #[arg(long, conflicts_with = "config")]
+        profile: Option<String>,
+    },
 }
 
 fn main() -> Result<(), Box<dyn Error>> {
     let cli = Args::parse();
 
+    // Work in real pixels on every monitor. Otherwise Windows scales the
+    // coordinates winmgr sees and sets for monitors other than the primary.
+    unsafe {
+        let _ = SetProcessDpiAwarenessContext(DPI_AWARENESS_CONTEXT_PER_MONITOR_AWARE_V2);
+    }
+
+    if cli.portable {
+        portable::force();
+    }
+
     match cli.command {
         Some(Command::Install) => {
             install_autostart()?;
@@ -62,18 +411,421 @@ fn main() -> Result<(), Box<dyn Error>> {
         Some(Command::Uninstall) => {
             uninstall_autostart()?;
         }
-        Some(Command::Run) | None => {
-            let Some(config) = get_config() else {
-                eprintln!("Failed to get config");
+        Some(Command::Init) => init::run()?,
+        Some(Command::Schema) => {
+            attach_console();
+
+            let schema = schemars::schema_for!(Config);
+            println!("{}", serde_json::to_string_pretty(&schema)?);
+        }
+        Some(Command::Validate { path, json }) => validate(path, json)?,
+        Some(Command::Run {
+            config,
+            profile,
+            minimal,
+            replace,
+        }) => run(config, profile, minimal, replace)?,
+        Some(Command::Config { command }) => match command {
+            ConfigCommand::Edit { config, profile } => edit_config(config, profile)?,
+            ConfigCommand::Migrate { config, profile } => migrate_config(config, profile)?,
+        },
+        Some(Command::Apply { layout }) => {
+            let layout = parse_layout(&layout)?;
+            control(ipc::Request::ApplyLayout {
+                layout,
+                hwnd: None,
+                window: None,
+            });
+        }
+        Some(Command::Move {
+            layout,
+            title,
+            exe,
+            class,
+            hwnd,
+        }) => {
+            let layout = parse_layout(&layout)?;
+            let hwnd = hwnd.as_deref().map(parse_hwnd).transpose()?;
+            let window = (title.is_some() || exe.is_some() || class.is_some())
+                .then_some(WindowMatch { exe, class, title });
+
+            control(ipc::Request::ApplyLayout {
+                layout,
+                hwnd,
+                window,
+            });
+        }
+        Some(Command::Reload) => control(ipc::Request::Reload),
+        Some(Command::Pause) => control(ipc::Request::Pause),
+        Some(Command::Resume) => control(ipc::Request::Resume),
+        Some(Command::Profile { name }) => control(ipc::Request::SwitchProfile { name }),
+        Some(Command::ToggleKeybinds { name, on, off }) => {
+            let enabled = (on || off).then_some(on);
+            control(ipc::Request::ToggleKeybinds { name, enabled });
+        }
+        Some(Command::Quit) => control(ipc::Request::Quit),
+        Some(Command::Stop) => {
+            attach_console();
+
+            match unsafe { instance::stop()? } {
+                true => println!("Stopped winmgr"),
+                false => println!("winmgr wasn't running"),
+            }
+        }
+        Some(Command::Status { json }) => status(json)?,
+        Some(Command::Snapshot { command }) => match command {
+            SnapshotCommand::Save { name } => snapshot::save(&name)?,
+            SnapshotCommand::Restore { name } => snapshot::restore(&name)?,
+            SnapshotCommand::List { json } => snapshot::list(json)?,
+        },
+        Some(Command::ListWindows { all, json }) => list::windows(all, json)?,
+        Some(Command::ListMonitors { json }) => list::monitors(json)?,
+        Some(Command::Keys {
+            config,
+            profile,
+            json,
+        }) => keys::run(config, profile, json)?,
+        None => run(None, None, false, false)?,
+    }
+
+    Ok(())
+}
+
+/// Print to the console winmgr was started from. Release builds have no
+/// console of their own.
+fn attach_console() {
+    unsafe {
+        let _ = AttachConsole(ATTACH_PARENT_PROCESS);
+    }
+}
+
+/// A layout given on the command line, either by name or as JSON
+fn parse_layout(layout: &str) -> Result<Layout, Box<dyn Error>> {
+    let value = match layout.trim_start().starts_with('{') {
+        true => serde_json::from_str(layout)?,
+        false => serde_json::Value::String(layout.to_owned()),
+    };
+
+    serde_json::from_value(value).map_err(|_| format!("Unknown layout {layout}").into())
+}
+
+/// A window handle given on the command line, in hex with `0x` or decimal
+fn parse_hwnd(hwnd: &str) -> Result<isize, Box<dyn Error>> {
+    let parsed = match hwnd.strip_prefix("0x") {
+        Some(hex) => isize::from_str_radix(hex, 16),
+        None => hwnd.parse(),
+    };
+
+    parsed.map_err(|_| format!("Invalid window handle {hwnd}").into())
+}
+
+/// Send a request to the running winmgr and exit with a code telling how it went
+fn control(request: ipc::Request) -> ! {
+    attach_console();
+
+    match ipc::send(&request) {
+        Ok(response) if response.ok => process::exit(0),
+        Ok(response) => {
+            eprintln!("{}", response.error.unwrap_or_default());
+            process::exit(EXIT_REFUSED);
+        }
+        Err(err) => {
+            eprintln!("{err}");
+            process::exit(EXIT_NOT_RUNNING);
+        }
+    }
+}
+
+fn status(json: bool) -> Result<(), Box<dyn Error>> {
+    attach_console();
+
+    let state = match ipc::send(&ipc::Request::QueryState) {
+        Ok(response) => response.state.ok_or("winmgr didn't report its state")?,
+        Err(_) => {
+            match json {
+                true => println!("{}", serde_json::json!({ "running": false })),
+                false => println!("winmgr is not running"),
+            }
+
+            process::exit(EXIT_NOT_RUNNING);
+        }
+    };
+
+    if json {
+        let mut report = serde_json::to_value(&state)?;
+        report["running"] = true.into();
+
+        println!("{}", serde_json::to_string_pretty(&report)?);
+        return Ok(());
+    }
+
+    println!(
+        "winmgr is running, up {}",
+        describe_duration(state.uptime_secs)
+    );
+    println!("Config:  {}", state.config.display());
+    println!("Profile: {}", state.profile.as_deref().unwrap_or("default"));
+    println!("Layer:   {}", state.layer.as_deref().unwrap_or("default"));
+    println!("Paused:  {}", if state.paused { "yes" } else { "no" });
+
+    if !state.disabled.is_empty() {
+        println!("Off:     {}", state.disabled.join(", "));
+    }
+    println!(
+        "Hotkeys: {} registered, {} failed",
+        state.registered, state.failed
+    );
+
+    if !state.recent_actions.is_empty() {
+        println!("Recent actions:");
+
+        for recent in state.recent_actions.iter().rev() {
+            println!(
+                "  {:>8} ago  {}",
+                describe_duration(recent.secs_ago),
+                recent.action
+            );
+        }
+    }
+
+    Ok(())
+}
+
+/// Roughly how long a number of seconds is, e.g. `2h 5m`
+fn describe_duration(secs: u64) -> String {
+    match secs {
+        0..60 => format!("{secs}s"),
+        60..3600 => format!("{}m {}s", secs / 60, secs % 60),
+        3600..86400 => format!("{}h {}m", secs / 3600, secs % 3600 / 60),
+        _ => format!("{}d {}h", secs / 86400, secs % 86400 / 3600),
+    }
+}
+
+fn validate(config_path: Option<PathBuf>, json: bool) -> Result<(), Box<dyn Error>> {
+    attach_console();
+
+    let config_path = match config_path {
+        Some(path) => path,
+        None => profile_config_path(None)?,
+    };
+
+    if !config_path.exists() {
+        return Err(format!("{} doesn't exist", config_path.display()).into());
+    }
+
+    if json {
+        // A config that can't be read is one more problem to report
+        let (problems, warnings) = match get_config(&config_path) {
+            Ok(config) => (validate::problems(&config), screen_warnings(&config)),
+            Err(err) => (vec![format!("Failed to read config: {err}")], Vec::new()),
+        };
+
+        let report = serde_json::json!({
+            "path": config_path,
+            "problems": problems,
+            "warnings": warnings,
+        });
+        println!("{}", serde_json::to_string_pretty(&report)?);
+
+        return match problems.is_empty() {
+            true => Ok(()),
+            false => Err(format!("Found {} problem(s)", problems.len()).into()),
+        };
+    }
+
+    check_config(&config_path)
+}
+
+/// Print the problems found in a config, failing if there are any, and
+/// warnings about the monitors connected right now, which don't fail it
+fn check_config(config_path: &Path) -> Result<(), Box<dyn Error>> {
+    let config = get_config(config_path)?;
+
+    let problems = validate::problems(&config);
+    let warnings = screen_warnings(&config);
+
+    if problems.is_empty() && warnings.is_empty() {
+        println!("{}: no problems found", config_path.display());
+        return Ok(());
+    }
+
+    println!("{}:", config_path.display());
+
+    for problem in &problems {
+        println!("  {problem}");
+    }
+
+    for warning in &warnings {
+        println!("  warning: {warning}");
+    }
+
+    match problems.is_empty() {
+        true => Ok(()),
+        false => Err(format!("Found {} problem(s)", problems.len()).into()),
+    }
+}
+
+/// Screen problems for the monitors connected now. They're only warnings,
+/// since the config may be meant for other monitors, e.g. when docked.
+fn screen_warnings(config: &Config) -> Vec<String> {
+    unsafe { validate::screen_problems(config, &monitor_infos(), &display::friendly_names()) }
+}
+
+fn edit_config(
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    attach_console();
+
+    let config_path = match config_path {
+        Some(path) => path,
+        None => profile_config_path(profile.as_deref())?,
+    };
+
+    create_config(&config_path, false)?;
+
+    match env::var("EDITOR") {
+        Ok(editor) => {
+            // `$EDITOR` may carry arguments, e.g. `code --wait`
+            let mut words = editor.split_whitespace();
+            let program = words.next().ok_or("EDITOR is empty")?;
+
+            let status = process::Command::new(program)
+                .args(words)
+                .arg(&config_path)
+                .status()
+                .map_err(|err| format!("Failed to start {program}: {err}"))?;
+
+            if !status.success() {
+                return Err(format!("{program} exited with {status}").into());
+            }
+        }
+        Err(_) => {
+            if !unsafe { shell_edit(&config_path)? } {
+                // Handed to an editor that was already open, so there is
+                // nothing to wait for
+                println!(
+                    "Opened {} in the editor, a running winmgr reloads it when it's saved",
+                    config_path.display()
+                );
                 return Ok(());
-            };
+            }
+        }
+    }
+
+    check_config(&config_path)?;
+
+    // Nothing to reload if winmgr isn't running
+    if let Ok(response) = ipc::send(&ipc::Request::Reload) {
+        match response.ok {
+            true => println!("Reloaded the running winmgr"),
+            false => return Err(response.error.unwrap_or_default().into()),
+        }
+    }
+
+    Ok(())
+}
+
+/// Open a file in the editor Windows associates with it, or else the program
+/// that opens it, and wait for that to exit. Returns false if there was no
+/// process to wait for.
+unsafe fn shell_edit(path: &Path) -> Result<bool, Box<dyn Error>> {
+    let file = HSTRING::from(path);
+    let mut error = None;
+
+    for verb in [w!("edit"), w!("open")] {
+        let mut info = SHELLEXECUTEINFOW {
+            cbSize: size_of::<SHELLEXECUTEINFOW>() as u32,
+            fMask: SEE_MASK_NOCLOSEPROCESS,
+            lpVerb: verb,
+            lpFile: PCWSTR(file.as_ptr()),
+            nShow: SW_SHOWNORMAL.0,
+            ..Default::default()
+        };
 
-            let registry = KeyBindRegistry::new(config);
+        match ShellExecuteExW(&mut info) {
+            Ok(()) if info.hProcess.is_invalid() => return Ok(false),
+            Ok(()) => {
+                WaitForSingleObject(info.hProcess, INFINITE);
+                let _ = CloseHandle(info.hProcess);
+                return Ok(true);
+            }
+            Err(err) => error = Some(err),
+        }
+    }
+
+    Err(format!(
+        "Failed to open {} in an editor: {}",
+        path.display(),
+        error.map(|err| err.to_string()).unwrap_or_default()
+    )
+    .into())
+}
+
+fn run(
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+    minimal: bool,
+    replace: bool,
+) -> Result<(), Box<dyn Error>> {
+    // The instance mutex stays held until winmgr exits
+    if let Err(err) = unsafe { instance::acquire(replace) } {
+        if let Some(notification) = report::problems("winmgr", &[err.to_string()]) {
+            let _ = notification.join();
+        }
+
+        return Err(err);
+    }
+
+    let config_path = match config_path {
+        Some(path) => path,
+        None => match profile_config_path(profile.as_deref()) {
+            Ok(path) => path,
+            Err(err) => {
+                eprintln!("Failed to get config: {err}");
+                return Ok(());
+            }
+        },
+    };
+
+    let config = match create_config(&config_path, minimal)
+        .and_then(|()| upgrade_config(&config_path))
+        .and_then(|_| get_config(&config_path))
+    {
+        Ok(config) => config,
+        Err(err) => {
+            let problems = [format!("Failed to read config: {err}")];
+
+            if let Some(notification) = report::problems("winmgr config problems", &problems) {
+                let _ = notification.join();
+            }
+
+            return Err("The config could not be read".into());
+        }
+    };
+
+    let problems = validate::combo_problems(&config);
+
+    if let Some(notification) = report::problems("winmgr config problems", &problems) {
+        let _ = notification.join();
+
+        return Err("The config has invalid keys or modifiers".into());
+    }
 
-            registry.run();
+    let mut registry = KeyBindRegistry::new(config, config_path, profile);
+
+    let notification = registry.report();
+
+    if registry.map.is_empty() && !registry.keybinds.is_empty() {
+        if let Some(notification) = notification {
+            let _ = notification.join();
         }
+
+        return Err("None of the configured keybinds could be registered".into());
     }
 
+    registry.run();
+
     Ok(())
 }
 
@@ -81,7 +833,10 @@ fn install_autostart() -> io::Result<()> {
     let exe_path = env::current_exe()?;
     let exe_str = exe_path.display().to_string();
 
-    let command = format!("\"{}\" run", exe_str);
+    let command = match portable::dir() {
+        Some(_) => format!("\"{}\" --portable run", exe_str),
+        None => format!("\"{}\" run", exe_str),
+    };
 
     let hkcu = RegKey::predef(HKEY_CURRENT_USER);
     let (run_key, _) = hkcu.create_subkey(RUN_KEY_PATH)?;
@@ -98,69 +853,948 @@ fn uninstall_autostart() -> io::Result<()> {
     Ok(())
 }
 
-fn get_config() -> Option<Config> {
-    let Some(dirs) = UserDirs::new() else {
-        eprintln!("Failed to get user home directory");
-        return None;
+/// `%APPDATA%\winmgr\config.json` if there is one, otherwise `config.toml`
+/// in the same directory. Profiles are `config.<profile>.json` or `.toml`
+/// alongside. A config left in the home directory by older versions is moved
+/// there. In portable mode the config is `winmgr.json` or `winmgr.toml` next
+/// to the executable instead.
+fn profile_config_path(profile: Option<&str>) -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(dir) = portable::dir() {
+        let stem = match profile {
+            Some(profile) => format!("winmgr.{profile}"),
+            None => "winmgr".to_owned(),
+        };
+
+        let json_path = dir.join(format!("{stem}.json"));
+
+        return match json_path.exists() {
+            true => Ok(json_path),
+            false => Ok(dir.join(format!("{stem}.toml"))),
+        };
+    }
+
+    let base = BaseDirs::new().ok_or("Failed to get user config directory")?;
+    let dir = base.config_dir().join("winmgr");
+
+    let stem = match profile {
+        Some(profile) => format!("config.{profile}"),
+        None => "config".to_owned(),
     };
 
-    let config_path = dirs.home_dir().join("winmgr.json");
+    let json_path = dir.join(format!("{stem}.json"));
+    let legacy_path = base.home_dir().join("winmgr.json");
 
-    let config: Config = match config_path.exists() {
-        true => serde_json::from_reader(File::open(config_path).ok()?).ok()?,
-        false => {
-            let new_config = Config::default();
-            serde_json::to_writer_pretty(File::create(config_path).ok()?, &new_config).ok()?;
-            new_config
+    if profile.is_none() && !json_path.exists() && legacy_path.exists() {
+        fs::create_dir_all(&dir)?;
+
+        if fs::rename(&legacy_path, &json_path).is_err() {
+            fs::copy(&legacy_path, &json_path)?;
         }
-    };
+    }
 
-    Some(config)
+    match json_path.exists() {
+        true => Ok(json_path),
+        false => Ok(dir.join(format!("{stem}.toml"))),
+    }
 }
 
-#[derive(Debug, Default, Serialize, Deserialize)]
-struct Config {
-    margin: u8,
-    keybinds: Vec<KeyBind>,
+fn modified(path: &Path) -> Option<SystemTime> {
+    fs::metadata(path).and_then(|meta| meta.modified()).ok()
 }
 
-#[derive(Debug, Serialize, Deserialize)]
-struct KeyBind {
-    modifiers: Vec<HexModifier>,
-    key: HexVirtualKey,
-    layout: Layout,
+/// Create the config if it's missing, with the starter keybinds unless
+/// `minimal`. TOML configs keep the comments of the template.
+fn create_config(config_path: &Path, minimal: bool) -> Result<(), Box<dyn Error>> {
+    if config_path.exists() {
+        return Ok(());
+    }
+
+    write_config(config_path, &config_template(minimal))
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct HexModifier(pub String);
+/// Text of a new TOML config, with the starter keybinds unless `minimal`
+fn config_template(minimal: bool) -> String {
+    let keybinds = match minimal {
+        true => MINIMAL_KEYBINDS,
+        false => STARTER_KEYBINDS,
+    };
 
-impl From<&HexModifier> for HOT_KEY_MODIFIERS {
-    fn from(value: &HexModifier) -> Self {
-        let without_prefix = value.0.trim_start_matches("0x");
-        let int = u32::from_str_radix(without_prefix, 16).expect("invalid hex");
-        HOT_KEY_MODIFIERS(int)
-    }
+    format!("{DEFAULT_TOML_CONFIG}{keybinds}")
 }
 
-#[derive(Debug, Clone, Serialize, Deserialize)]
-struct HexVirtualKey(pub String);
+/// Write a TOML config template, converting it to JSON for a `.json` path
+fn write_config(config_path: &Path, template: &str) -> Result<(), Box<dyn Error>> {
+    if let Some(dir) = config_path.parent() {
+        fs::create_dir_all(dir)?;
+    }
 
-impl From<&HexVirtualKey> for VIRTUAL_KEY {
-    fn from(value: &HexVirtualKey) -> Self {
-        let without_prefix = value.0.trim_start_matches("0x");
-        let int = u16::from_str_radix(without_prefix, 16).expect("invalid hex");
-        VIRTUAL_KEY(int)
+    match is_toml(config_path) {
+        true => fs::write(config_path, template)?,
+        false => {
+            let config: Config = toml::from_str(template)?;
+            serde_json::to_writer_pretty(File::create(config_path)?, &config)?;
+        }
     }
+
+    Ok(())
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
-#[serde(untagged)]
-enum Layout {
-    Custom(CustomLayout),
-    Default(DefaultLayout),
+fn is_toml(config_path: &Path) -> bool {
+    config_path
+        .extension()
+        .is_some_and(|ext| ext.eq_ignore_ascii_case("toml"))
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+/// Read the config, as TOML or JSON depending on the file extension. A config
+/// written for an older version is upgraded in memory, leaving the file as it
+/// is.
+fn get_config(config_path: &Path) -> Result<Config, Box<dyn Error>> {
+    let is_toml = is_toml(config_path);
+    let text = fs::read_to_string(config_path)?;
+
+    let mut document: serde_json::Value = parse_config(config_path, &text, is_toml)?;
+    let current = migrate::version(&document) == migrate::CONFIG_VERSION;
+
+    migrate::migrate(&mut document)?;
+
+    if current && document.get("include").is_none() {
+        // Straight from the text, so that errors point at a line
+        return parse_config(config_path, &text, is_toml);
+    }
+
+    let document = include::resolve(config_path, document)?;
+
+    Ok(serde_json::from_value(document)?)
+}
+
+/// Upgrade a config written for an older version in place, keeping the
+/// original next to it. Returns the version it was upgraded from, if it was.
+fn upgrade_config(config_path: &Path) -> Result<Option<u32>, Box<dyn Error>> {
+    let is_toml = is_toml(config_path);
+    let text = fs::read_to_string(config_path)?;
+
+    let mut document: serde_json::Value = parse_config(config_path, &text, is_toml)?;
+    let version = migrate::version(&document);
+
+    if version == migrate::CONFIG_VERSION {
+        return Ok(None);
+    }
+
+    migrate::migrate(&mut document)?;
+
+    // Keep the old file, since rewriting it drops comments and formatting
+    let mut backup = config_path.as_os_str().to_owned();
+    backup.push(format!(".v{version}.bak"));
+    fs::copy(config_path, &backup)?;
+
+    let text = match is_toml {
+        true => toml::to_string_pretty(&document)?,
+        false => serde_json::to_string_pretty(&document)?,
+    };
+
+    fs::write(config_path, text)?;
+
+    Ok(Some(version))
+}
+
+/// Upgrade a config for `winmgr config migrate`
+fn migrate_config(
+    config_path: Option<PathBuf>,
+    profile: Option<String>,
+) -> Result<(), Box<dyn Error>> {
+    attach_console();
+
+    let config_path = match config_path {
+        Some(path) => path,
+        None => profile_config_path(profile.as_deref())?,
+    };
+
+    match upgrade_config(&config_path)? {
+        Some(version) => println!(
+            "Upgraded {} from version {version} to {}, keeping the original as {}.v{version}.bak",
+            config_path.display(),
+            migrate::CONFIG_VERSION,
+            config_path.display()
+        ),
+        None => println!("{} is already up to date", config_path.display()),
+    }
+
+    Ok(())
+}
+
+/// Read a config file as an untyped document, upgraded to the current version
+/// in memory
+fn load_document(path: &Path) -> Result<serde_json::Value, Box<dyn Error>> {
+    let mut document = parse_config(path, &fs::read_to_string(path)?, is_toml(path))?;
+
+    migrate::migrate(&mut document)?;
+
+    Ok(document)
+}
+
+fn parse_config<T: DeserializeOwned>(
+    config_path: &Path,
+    text: &str,
+    is_toml: bool,
+) -> Result<T, Box<dyn Error>> {
+    match is_toml {
+        true => Ok(toml::from_str(text)?),
+        false => jsonc::from_str(config_path, text),
+    }
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct Config {
+    /// Format version of the config, so that older configs can be upgraded
+    #[serde(default)]
+    version: u32,
+    /// Other config files to merge in, relative to this one
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    include: Vec<String>,
+    margin: u8,
+    /// Space between adjacent windows snapped by layouts
+    #[serde(default)]
+    gap: u8,
+    /// Share of the width taken by `LeftHalf`, and of the height by `TopHalf`.
+    /// The opposite halves take the rest.
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    split_ratio: Option<f32>,
+    #[serde(default = "default_chord_timeout_ms")]
+    chord_timeout_ms: u32,
+    /// Pixels added or removed per key press in resize mode and per `ResizeBy` step
+    #[serde(default = "default_resize_step")]
+    resize_step: i32,
+    /// Pixels moved per `MoveBy` step
+    #[serde(default = "default_move_step")]
+    move_step: i32,
+    keybinds: Vec<KeyBind>,
+    /// Named sets of keybinds, active on top of `keybinds` when switched to
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    layers: BTreeMap<String, Vec<KeyBind>>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    mousebinds: Vec<MouseBind>,
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    gestures: Vec<Gesture>,
+    /// Most time allowed between the two taps of a double-tap gesture
+    #[serde(default = "default_double_tap_ms")]
+    double_tap_ms: u32,
+    /// Bring windows to the foreground after moving them, instead of leaving
+    /// focus and stacking order as they were
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    activate: bool,
+    /// Move the mouse cursor to the middle of windows that keybinds move or
+    /// focus
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    warp_cursor: bool,
+    /// Put windows back where they were the last time the same monitors were
+    /// connected, whenever monitors change
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    restore_on_reconnect: bool,
+    /// Apply layouts again when the work area they were applied to changes,
+    /// such as when the taskbar moves
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    follow_work_area: bool,
+    /// Monitor to place layouts on when a keybind doesn't set `monitor`
+    #[serde(default, skip_serializing_if = "is_default")]
+    monitor_from: MonitorFrom,
+    /// Settings for particular monitors, by device name (e.g. `DISPLAY1`) or
+    /// by number counting from 1
+    #[serde(default, skip_serializing_if = "BTreeMap::is_empty")]
+    monitors: BTreeMap<String, MonitorSettings>,
+    /// Windows that keybinds never act on, such as games and remote desktops
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    exclusions: Vec<WindowMatch>,
+    /// Settings for particular applications' windows. Where several rules
+    /// match a window, each setting comes from the first that sets it.
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    rules: Vec<Rule>,
+}
+
+/// Overrides of global settings on one monitor
+#[derive(Debug, Default, Serialize, Deserialize, JsonSchema)]
+struct MonitorSettings {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    margin: Option<u8>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    gap: Option<u8>,
+}
+
+impl Default for Config {
+    fn default() -> Self {
+        Self {
+            version: migrate::CONFIG_VERSION,
+            include: Vec::new(),
+            margin: 0,
+            gap: 0,
+            split_ratio: None,
+            chord_timeout_ms: default_chord_timeout_ms(),
+            resize_step: default_resize_step(),
+            move_step: default_move_step(),
+            keybinds: Vec::new(),
+            layers: BTreeMap::new(),
+            mousebinds: Vec::new(),
+            gestures: Vec::new(),
+            double_tap_ms: default_double_tap_ms(),
+            activate: false,
+            warp_cursor: false,
+            restore_on_reconnect: false,
+            follow_work_area: false,
+            monitor_from: MonitorFrom::Window,
+            monitors: BTreeMap::new(),
+            exclusions: Vec::new(),
+            rules: Vec::new(),
+        }
+    }
+}
+
+fn is_default<T: Default + PartialEq>(value: &T) -> bool {
+    *value == T::default()
+}
+
+fn default_chord_timeout_ms() -> u32 {
+    1000
+}
+
+fn default_resize_step() -> i32 {
+    20
+}
+
+fn default_move_step() -> i32 {
+    20
+}
+
+fn default_double_tap_ms() -> u32 {
+    300
+}
+
+fn default_span() -> u32 {
+    1
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct KeyBind {
+    /// Identifies the keybind, or a family of keybinds sharing the name, for
+    /// switching them off and on at runtime
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    name: Option<String>,
+    modifiers: Vec<HexModifier>,
+    key: HexVirtualKey,
+    /// Keep firing while the key is held down
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    allow_repeat: bool,
+    #[serde(default, alias = "layout", skip_serializing_if = "Option::is_none")]
+    action: Option<Action>,
+    /// Only run this keybind when the foreground window matches
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    when: Option<WindowMatch>,
+    /// Layouts applied in turn on repeated presses, in place of `action`
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    cycle: Vec<Layout>,
+    /// Follow-up keys that become active after this keybind is pressed
+    #[serde(default, skip_serializing_if = "Vec::is_empty")]
+    chord: Vec<ChordBind>,
+    #[serde(flatten)]
+    options: BindOptions,
+}
+
+/// Per-binding overrides of global settings
+#[derive(Debug, Clone, Default, Serialize, Deserialize, JsonSchema)]
+struct BindOptions {
+    /// Pixels per step for `MoveBy` and `ResizeBy`, instead of `move_step`
+    /// and `resize_step`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    step: Option<i32>,
+    /// Which window the action applies to
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    target: Option<Target>,
+    /// Share of the work area covered by half and two-thirds layouts
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    ratio: Option<f32>,
+    /// Monitor to place layouts on, counting from 1 or `next`/`prev`, instead
+    /// of the window's own
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    monitor: Option<MonitorTarget>,
+    /// Monitor to place layouts on when `monitor` isn't set, instead of
+    /// `monitor_from`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    monitor_from: Option<MonitorFrom>,
+    /// Fit the window into layouts at its current aspect ratio instead of
+    /// stretching it
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    keep_aspect: bool,
+    /// Bring the window to the foreground after moving it, instead of `activate`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    activate: Option<bool>,
+    /// Move the mouse cursor to the window, instead of `warp_cursor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    warp_cursor: Option<bool>,
+}
+
+#[derive(Debug, Clone, Copy, Default, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Target {
+    /// The focused window
+    #[default]
+    Foreground,
+    /// The top-level window under the mouse cursor
+    Cursor,
+}
+
+/// The monitor that layouts go on when no monitor is named, and that `next`
+/// and `prev` count from
+#[derive(Debug, Clone, Copy, Default, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum MonitorFrom {
+    /// The monitor the window is on
+    #[default]
+    Window,
+    /// The monitor the mouse cursor is on
+    Cursor,
+    /// The primary monitor
+    Primary,
+}
+
+/// A monitor picked by number, counting from 1 left to right, relative to
+/// the window's own, or by name
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum MonitorTarget {
+    Number(u32),
+    Adjacent(Adjacent),
+    /// Device name such as `DISPLAY1`, or friendly name such as `DELL U2720Q`
+    Name(String),
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Adjacent {
+    /// The monitor to the right, wrapping round to the leftmost
+    Next,
+    /// The monitor to the left, wrapping round to the rightmost
+    Prev,
+}
+
+/// Matches a window by the image name of its process, its class name and/or
+/// a regex on its title. The comparisons are case-insensitive. An `exe` with a directory in it,
+/// e.g. `%LOCALAPPDATA%\Programs\app.exe`, is matched against the full path.
+#[derive(Debug, Clone, PartialEq, Serialize, Deserialize, JsonSchema)]
+struct WindowMatch {
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    exe: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    class: Option<String>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    title: Option<String>,
+}
+
+impl WindowMatch {
+    unsafe fn matches(&self, hwnd: HWND) -> bool {
+        if let Some(exe) = &self.exe {
+            let exe = expand::expand(exe);
+
+            let actual = if exe.contains(['\\', '/']) {
+                window::process_path(hwnd).map(|path| path.replace('/', "\\"))
+            } else {
+                window::process_name(hwnd)
+            };
+
+            if !actual.is_some_and(|actual| actual.eq_ignore_ascii_case(&exe.replace('/', "\\"))) {
+                return false;
+            }
+        }
+
+        if let Some(class) = &self.class
+            && !window::class_name(hwnd).eq_ignore_ascii_case(class)
+        {
+            return false;
+        }
+
+        if let Some(title) = &self.title
+            && !self
+                .title_regex()
+                .is_some_and(|regex| regex.is_match(&window::title(hwnd)))
+        {
+            return false;
+        }
+
+        true
+    }
+
+    fn title_regex(&self) -> Option<Regex> {
+        RegexBuilder::new(self.title.as_deref()?)
+            .case_insensitive(true)
+            .build()
+            .ok()
+    }
+}
+
+/// How winmgr treats the windows of one application
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct Rule {
+    #[serde(flatten)]
+    window: WindowMatch,
+    /// Layout given to the window when it opens and by `ApplyRules`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    layout: Option<Layout>,
+    /// Monitor to place layouts on for the window when a keybind doesn't set
+    /// `monitor`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    monitor: Option<MonitorTarget>,
+    /// Leave the window out when arranging the windows on its monitor
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    float: bool,
+    /// Keep the window above others, set when it opens and by `ApplyRules`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    always_on_top: bool,
+    /// Opacity in percent, set when the window opens and by `ApplyRules`
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    opacity: Option<u8>,
+    /// Never act on the window, as if it were in `exclusions`
+    #[serde(default, skip_serializing_if = "std::ops::Not::not")]
+    exclude: bool,
+}
+
+impl KeyBind {
+    /// Layouts to step through on repeated presses. A corner layout without an
+    /// explicit `cycle` goes clockwise round the corners.
+    fn cycle_layouts(&self) -> Vec<Layout> {
+        if !self.cycle.is_empty() {
+            return self.cycle.clone();
+        }
+
+        match self.action {
+            Some(Action::ApplyLayout(Layout::Default(layout))) => {
+                iter::successors(layout.next_corner().map(|_| layout), |l| l.next_corner())
+                    .take(4)
+                    .map(Layout::Default)
+                    .collect()
+            }
+            _ => Vec::new(),
+        }
+    }
+
+    fn combo(&self) -> String {
+        describe_combo(&self.modifiers, &self.key)
+    }
+
+    /// Modifiers and virtual key, or why they aren't real ones
+    fn parse_combo(&self) -> Result<(HOT_KEY_MODIFIERS, VIRTUAL_KEY), String> {
+        parse_combo(&self.modifiers, &self.key)
+    }
+
+    /// What the keybind does, as listed by `winmgr keys`
+    fn describe_action(&self) -> String {
+        if !self.chord.is_empty() {
+            return format!("chord of {} keys", self.chord.len());
+        }
+
+        if !self.cycle.is_empty() {
+            return format!("cycle of {} layouts", self.cycle.len());
+        }
+
+        match &self.action {
+            Some(action) => serde_json::to_string(action).unwrap_or_default(),
+            None => String::new(),
+        }
+    }
+
+    fn info(
+        &self,
+        layer: Option<&str>,
+        status: ipc::KeyStatus,
+        error: Option<String>,
+    ) -> ipc::KeyInfo {
+        ipc::KeyInfo {
+            combo: self.combo(),
+            layer: layer.map(str::to_owned),
+            name: self.name.clone(),
+            action: self.describe_action(),
+            when: self.when.clone(),
+            status,
+            error,
+        }
+    }
+
+    /// Modifiers and virtual key, for comparing combos, or `None` if the
+    /// combo is invalid
+    fn combo_id(&self) -> Option<(u32, u16)> {
+        self.parse_combo().ok().map(|(mods, key)| (mods.0, key.0))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct ChordBind {
+    #[serde(default)]
+    modifiers: Vec<HexModifier>,
+    key: HexVirtualKey,
+    #[serde(alias = "layout")]
+    action: Action,
+}
+
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct MouseBind {
+    modifiers: Vec<HexModifier>,
+    button: MouseButton,
+    #[serde(alias = "layout")]
+    action: Action,
+    #[serde(flatten)]
+    options: BindOptions,
+}
+
+/// Runs an action when a modifier key is tapped twice on its own
+#[derive(Debug, Serialize, Deserialize, JsonSchema)]
+struct Gesture {
+    double_tap: HexModifier,
+    #[serde(alias = "layout")]
+    action: Action,
+    #[serde(flatten)]
+    options: BindOptions,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+enum MouseButton {
+    Left,
+    Right,
+    Middle,
+    XButton1,
+    XButton2,
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+enum Action {
+    Maximize,
+    /// Restore the window if it is maximized, otherwise do nothing
+    RestoreIfMaximized,
+    /// Maximize the window, or restore it to where it was before
+    MaximizeToggle,
+    Minimize,
+    /// Minimize every other window on the monitor
+    MinimizeOthers,
+    /// Minimize every window to show the desktop, or restore the windows it
+    /// minimized
+    ToggleDesktop,
+    Close,
+    /// Close the window, and offer to end its process if it hasn't closed
+    /// after a few seconds
+    ForceClose,
+    CenterKeepSize,
+    ToggleAlwaysOnTop,
+    /// Make the window more opaque by a step
+    OpacityUp,
+    /// Make the window more transparent by a step
+    OpacityDown,
+    /// Set the window's opacity in percent
+    OpacitySet(u8),
+    /// Give the window the layout, always-on-top and opacity its rules set
+    ApplyRules,
+    /// Put the window back where it was before the last move by winmgr
+    Undo,
+    /// Put the window back where it was before winmgr first moved it
+    RestoreOriginal,
+    /// Move the window by (x, y) steps
+    MoveBy(i32, i32),
+    /// Grow or shrink the window by (width, height) steps
+    ResizeBy(i32, i32),
+    FocusLeft,
+    FocusRight,
+    FocusUp,
+    FocusDown,
+    /// Pick a window to focus from a list filtered by typing part of its
+    /// title or process name
+    Switcher,
+    /// Focus the window that had focus before the current one, flipping
+    /// between the two on repeated presses
+    FocusLast,
+    /// Move the window onto another monitor, keeping its position and size
+    /// relative to the work area
+    SendToMonitor(MonitorTarget),
+    /// Move the window onto another monitor into the same layout it fills on
+    /// its own, or as `SendToMonitor` if it fills none
+    MirrorToMonitor(MonitorTarget),
+    /// Exchange places with the nearest window to the left
+    SwapLeft,
+    /// Exchange places with the nearest window to the right
+    SwapRight,
+    /// Stack every window on the monitor diagonally, the focused one in front
+    Cascade,
+    /// Lay out every window on the monitor in an even grid
+    TileAll,
+    /// Lay out every window on the monitor in a spiral of halves, the focused
+    /// one taking the largest
+    Spiral,
+    /// Remember the window in a numbered slot
+    TagWindow(u8),
+    /// Focus the window remembered in a numbered slot
+    JumpToTag(u8),
+    ResizeMode,
+    TogglePause,
+    /// Switch the keybinds with this name off, or back on
+    ToggleKeybinds(String),
+    /// Switch to a named keybind layer, or back to the base keybinds with `default`
+    SwitchLayer(String),
+    /// Switch to the config of a named profile, or back to the main config with `default`
+    SwitchProfile(String),
+    ReloadConfig,
+    Quit,
+    RunCommand {
+        program: String,
+        #[serde(default)]
+        args: Vec<String>,
+        /// Directory to start the program in, instead of winmgr's own
+        #[serde(default, skip_serializing_if = "Option::is_none")]
+        cwd: Option<String>,
+    },
+    /// Focus the topmost window of a process, or launch the program if it has
+    /// none open
+    FocusOrLaunch {
+        /// Image name of the process, or the path to the program
+        exe: String,
+        /// Arguments passed when launching
+        #[serde(default)]
+        args: Vec<String>,
+    },
+    /// Hide the window of a process when it's in the foreground, focus it
+    /// when it isn't, or show it again at `layout` and focus it, launching
+    /// the program if it isn't running, e.g. for a drop-down terminal
+    Scratchpad {
+        /// Image name of the process, or the path to the program
+        exe: String,
+        layout: Layout,
+    },
+    /// Strip the window's title bar and borders and cover the whole monitor
+    /// with it, or put it back
+    ToggleBorderless,
+    /// Roll the window up to its title bar, or back down
+    ToggleShade,
+    /// Show the window on every virtual desktop, or only on the current one
+    /// again
+    TogglePinWindow,
+    /// Switch taskbar auto-hide on or off, and fit the windows to the changed
+    /// work areas
+    ToggleTaskbarAutoHide,
+    /// Switch to a virtual desktop, counting from 1
+    SwitchDesktop(u32),
+    /// Move the window to a virtual desktop, counting from 1
+    SendToDesktop(u32),
+    /// Move the window to a virtual desktop and switch there with it
+    SendToDesktopAndFollow(u32),
+    /// Run several actions in order
+    Macro(Vec<Action>),
+    /// Wait this many milliseconds before the next step of a macro
+    Delay(u32),
+    /// Lay out the focused window and the most recently used windows after it,
+    /// one layout each, on the focused window's monitor
+    Arrange(Vec<Layout>),
+    /// Add the window to the column the layout covers, stacking it below the
+    /// windows already there
+    PushColumn(Layout),
+    /// Lay the window exactly over a window in the column the layout covers,
+    /// and bring it to the front, like a tab
+    StackOnto(Layout),
+    #[serde(untagged)]
+    ApplyLayout(Layout),
+}
+
+impl Action {
+    /// Whether the action moves or resizes the window it targets, so that it
+    /// can be undone
+    fn moves_window(&self) -> bool {
+        matches!(
+            self,
+            Action::ApplyLayout(_)
+                | Action::SendToMonitor(_)
+                | Action::MirrorToMonitor(_)
+                | Action::Maximize
+                | Action::RestoreIfMaximized
+                | Action::MaximizeToggle
+                | Action::RestoreOriginal
+                | Action::CenterKeepSize
+                | Action::MoveBy(..)
+                | Action::ResizeBy(..)
+                | Action::SwapLeft
+                | Action::SwapRight
+                | Action::Arrange(_)
+                | Action::PushColumn(_)
+                | Action::StackOnto(_)
+                | Action::Cascade
+                | Action::TileAll
+                | Action::Spiral
+        )
+    }
+
+    /// Whether the action brings another window to the foreground
+    fn focuses_window(&self) -> bool {
+        matches!(
+            self,
+            Action::FocusLeft
+                | Action::FocusRight
+                | Action::FocusUp
+                | Action::FocusDown
+                | Action::FocusLast
+                | Action::JumpToTag(_)
+                | Action::FocusOrLaunch { .. }
+        )
+    }
+
+    /// Whether the action operates on the foreground window
+    fn targets_window(&self) -> bool {
+        !matches!(
+            self,
+            Action::JumpToTag(_)
+                | Action::FocusLast
+                | Action::Switcher
+                | Action::ToggleDesktop
+                | Action::ToggleTaskbarAutoHide
+                | Action::ResizeMode
+                | Action::TogglePause
+                | Action::ToggleKeybinds(_)
+                | Action::SwitchLayer(_)
+                | Action::SwitchProfile(_)
+                | Action::ReloadConfig
+                | Action::Quit
+                | Action::RunCommand { .. }
+                | Action::FocusOrLaunch { .. }
+                | Action::Scratchpad { .. }
+                | Action::SwitchDesktop(_)
+                | Action::Macro(_)
+                | Action::Delay(_)
+        )
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct HexModifier(pub String);
+
+impl HexModifier {
+    fn parse(&self) -> Option<HOT_KEY_MODIFIERS> {
+        let without_prefix = self.0.trim_start_matches("0x");
+        let int = u32::from_str_radix(without_prefix, 16).ok()?;
+        Some(HOT_KEY_MODIFIERS(int))
+    }
+}
+
+#[derive(Debug, Clone, Serialize, Deserialize, JsonSchema)]
+struct HexVirtualKey(pub String);
+
+impl HexVirtualKey {
+    /// The virtual key, or why the name or code isn't one
+    fn parse(&self) -> Result<VIRTUAL_KEY, String> {
+        let name = &self.0;
+
+        if let Some(hex) = name.strip_prefix("0x") {
+            return u16::from_str_radix(hex, 16)
+                .map(VIRTUAL_KEY)
+                .map_err(|_| format!("invalid key code \"{name}\""));
+        }
+
+        // Bare hex codes predate key names
+        key_to_virtual_key(name)
+            .or_else(|| u16::from_str_radix(name, 16).ok().map(VIRTUAL_KEY))
+            .ok_or_else(|| format!("unknown key name \"{name}\""))
+    }
+}
+
+/// Map a key name (e.g. `A`, `F5`, `Left`, `Numpad3`, `;`) to its virtual key.
+/// Names are case-insensitive. Other single characters (e.g. `ö`, `#`) are
+/// looked up on the active keyboard layout, and `sc:<hex>` names a hardware
+/// scan code so the binding stays on the same physical key across layouts.
+fn key_to_virtual_key(name: &str) -> Option<VIRTUAL_KEY> {
+    let upper = name.to_ascii_uppercase();
+
+    if let Some(code) = upper.strip_prefix("SC:") {
+        let code = u32::from_str_radix(code.trim_start_matches("0X"), 16).ok()?;
+        let vk = unsafe { MapVirtualKeyW(code, MAPVK_VSC_TO_VK_EX) };
+
+        return (vk != 0).then_some(VIRTUAL_KEY(vk as u16));
+    }
+
+    let mut chars = name.chars();
+
+    if let (Some(c), None) = (chars.next(), chars.next())
+        && !c.is_ascii_alphanumeric()
+        && let Some(vk) = char_to_virtual_key(c)
+    {
+        return Some(vk);
+    }
+
+    if let [c] = upper.as_bytes()
+        && (c.is_ascii_uppercase() || c.is_ascii_digit())
+    {
+        return Some(VIRTUAL_KEY(*c as u16));
+    }
+
+    if let Some(n) = upper.strip_prefix('F').and_then(|n| n.parse::<u16>().ok()) {
+        return (1..=24).contains(&n).then(|| VIRTUAL_KEY(VK_F1.0 + n - 1));
+    }
+
+    if let Some(n) = upper
+        .strip_prefix("NUMPAD")
+        .and_then(|n| n.parse::<u16>().ok())
+    {
+        return (0..=9).contains(&n).then(|| VIRTUAL_KEY(VK_NUMPAD0.0 + n));
+    }
+
+    let key = match upper.as_str() {
+        "LEFT" => VK_LEFT,
+        "RIGHT" => VK_RIGHT,
+        "UP" => VK_UP,
+        "DOWN" => VK_DOWN,
+        "HOME" => VK_HOME,
+        "END" => VK_END,
+        "PAGEUP" => VK_PRIOR,
+        "PAGEDOWN" => VK_NEXT,
+        "INSERT" => VK_INSERT,
+        "DELETE" => VK_DELETE,
+        "SPACE" => VK_SPACE,
+        "ENTER" => VK_RETURN,
+        "ESCAPE" => VK_ESCAPE,
+        "TAB" => VK_TAB,
+        "BACKSPACE" => VK_BACK,
+        "NUMPADADD" => VK_ADD,
+        "NUMPADSUBTRACT" => VK_SUBTRACT,
+        "NUMPADMULTIPLY" => VK_MULTIPLY,
+        "NUMPADDIVIDE" => VK_DIVIDE,
+        "NUMPADDECIMAL" => VK_DECIMAL,
+        ";" => VK_OEM_1,
+        "=" => VK_OEM_PLUS,
+        "," => VK_OEM_COMMA,
+        "-" => VK_OEM_MINUS,
+        "." => VK_OEM_PERIOD,
+        "/" => VK_OEM_2,
+        "`" => VK_OEM_3,
+        "[" => VK_OEM_4,
+        "\\" => VK_OEM_5,
+        "]" => VK_OEM_6,
+        "'" => VK_OEM_7,
+        _ => return None,
+    };
+
+    Some(key)
+}
+
+/// Virtual key producing the character on the active keyboard layout,
+/// ignoring any shift state needed to type it
+fn char_to_virtual_key(c: char) -> Option<VIRTUAL_KEY> {
+    let mut buf = [0u16; 2];
+
+    let [unit] = c.encode_utf16(&mut buf) else {
+        return None;
+    };
+
+    let scan = unsafe { VkKeyScanW(*unit) };
+
+    (scan != -1).then_some(VIRTUAL_KEY((scan as u16) & 0xFF))
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+#[serde(untagged)]
+enum Layout {
+    Custom(CustomLayout),
+    Grid(GridLayout),
+    Default(DefaultLayout),
+}
+
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 enum DefaultLayout {
     LeftHalf,
     RightHalf,
@@ -172,25 +1806,312 @@ enum DefaultLayout {
     CenterSmall,
     CenterMedium,
     CenterLarge,
+    TopLeft,
+    TopRight,
+    BottomRight,
+    BottomLeft,
+    TopHalf,
+    BottomHalf,
+    TopThird,
+    MiddleThird,
+    BottomThird,
+    /// Centered, covering the given percentage of the work area
+    CenterPercent(u8),
 }
 
-#[derive(Debug, Clone, Copy, Serialize, Deserialize)]
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
 struct CustomLayout {
     x: i32,
     y: i32,
     w: i32,
     h: i32,
+    #[serde(default, skip_serializing_if = "Unit::is_pixels")]
+    unit: Unit,
+    #[serde(default, skip_serializing_if = "Span::is_monitor")]
+    span: Span,
+    /// Place the window on this monitor, counting from 1, with pixel
+    /// coordinates relative to its work area
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    monitor: Option<u32>,
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    relative_to: Option<Origin>,
+}
+
+/// Area that custom layout coordinates are relative to
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "snake_case")]
+enum Origin {
+    /// Work area of the monitor, which excludes the taskbar
+    WorkArea,
+    /// Whole monitor
+    Monitor,
+    /// Bounding rect of all monitors
+    VirtualScreen,
+}
+
+/// Area that percentage coordinates are relative to
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Span {
+    /// Work area of the window's monitor
+    #[default]
+    Monitor,
+    /// Bounding rect of all monitors
+    All,
+}
+
+impl Span {
+    fn is_monitor(&self) -> bool {
+        *self == Span::Monitor
+    }
+}
+
+#[derive(Debug, Clone, Copy, Default, PartialEq, Eq, Serialize, Deserialize, JsonSchema)]
+#[serde(rename_all = "lowercase")]
+enum Unit {
+    /// Absolute screen coordinates
+    #[default]
+    Pixels,
+    /// Percentages of the work area of the window's monitor
+    Percent,
+}
+
+impl Unit {
+    fn is_pixels(&self) -> bool {
+        *self == Unit::Pixels
+    }
+}
+
+impl CustomLayout {
+    /// Calculate (x, y, w, h)
+    unsafe fn calc(self, mi: &MONITORINFO) -> (i32, i32, i32, i32) {
+        let Some(area) = self.area(mi) else {
+            // Absolute screen coordinates
+            return (self.x, self.y, self.w, self.h);
+        };
+
+        match self.unit {
+            Unit::Pixels => (area.left + self.x, area.top + self.y, self.w, self.h),
+            Unit::Percent => {
+                let scale = |n: i32, size: i32| (n as i64 * size as i64 / 100) as i32;
+
+                let width = area.right - area.left;
+                let height = area.bottom - area.top;
+
+                (
+                    area.left + scale(self.x, width),
+                    area.top + scale(self.y, height),
+                    scale(self.w, width),
+                    scale(self.h, height),
+                )
+            }
+        }
+    }
+
+    /// What the coordinates are relative to, or `None` for plain screen
+    /// coordinates
+    fn measured_from(self) -> Option<Origin> {
+        match (self.relative_to, self.span) {
+            (Some(origin), _) => Some(origin),
+            (None, Span::All) => Some(Origin::VirtualScreen),
+            (None, Span::Monitor) if self.unit == Unit::Percent || self.monitor.is_some() => {
+                Some(Origin::WorkArea)
+            }
+            (None, Span::Monitor) => None,
+        }
+    }
+
+    /// Whether the coordinates are plain screen coordinates, measured from
+    /// the primary monitor's top-left corner
+    fn in_screen_coordinates(self) -> bool {
+        self.measured_from().is_none()
+    }
+
+    /// Rect the coordinates are relative to, or `None` for plain screen
+    /// coordinates. Percentages and layouts on a given monitor default to the
+    /// work area.
+    unsafe fn area(self, mi: &MONITORINFO) -> Option<RECT> {
+        Some(match self.measured_from()? {
+            Origin::WorkArea => mi.rcWork,
+            Origin::Monitor => mi.rcMonitor,
+            Origin::VirtualScreen => virtual_screen(),
+        })
+    }
+}
+
+/// Cells of a grid laid over the work area. Columns and rows count from 0.
+#[derive(Debug, Clone, Copy, Serialize, Deserialize, JsonSchema)]
+struct GridLayout {
+    cols: u32,
+    #[serde(default = "default_span")]
+    rows: u32,
+    col: u32,
+    #[serde(default)]
+    row: u32,
+    #[serde(default = "default_span")]
+    col_span: u32,
+    #[serde(default = "default_span")]
+    row_span: u32,
 }
 
 #[derive(Debug, Default)]
 struct KeyBindRegistry {
     cfg: Config,
-    map: IntMap<usize, usize>,
+    /// Where `cfg` was read from, for reloading
+    config_path: PathBuf,
+    /// Modification time of the config file when it was last read
+    config_modified: Option<SystemTime>,
+    /// Timer that reloads the config once it has stopped changing
+    reload_timer: Option<usize>,
+    /// Keybind indexes per hotkey id. Several keybinds share a hotkey when they
+    /// bind the same combo with different `when` clauses.
+    map: IntMap<usize, Vec<usize>>,
+    /// Keybinds of the active layer followed by the base keybinds
+    keybinds: Vec<KeyBind>,
+    /// Name of the active layer
+    layer: Option<String>,
+    /// Profile `config_path` belongs to, if not the main config
+    profile: Option<String>,
+    /// Conflicts in the config and keybinds that failed to register
+    problems: Vec<String>,
+    /// Why keybinds failed to register, per keybind index
+    failures: IntMap<usize, String>,
+    /// When winmgr started, for `winmgr status`
+    started: Instant,
+    /// Latest actions performed, with when they ran, oldest first
+    recent: VecDeque<(Instant, Action)>,
+    chord: Option<ActiveChord>,
+    resizing: bool,
+    paused: bool,
+    /// Names of keybinds switched off at runtime
+    disabled: HashSet<String>,
+    /// Position in a keybind's layout cycle, per window
+    cycles: IntMap<isize, CycleState>,
+    /// Windows remembered by `TagWindow`
+    tags: IntMap<u8, HWND>,
+    /// Macros waiting on a delay step
+    macros: Vec<PendingMacro>,
+    /// Windows on their way to another desktop
+    desktop_moves: Vec<PendingDesktopMove>,
+    closing: Vec<PendingClose>,
+    work_area_change: Option<PendingWorkArea>,
+    /// Monitors as of the last display change
+    displays: Vec<MONITORINFO>,
+    /// Friendly names of `displays`, as `display::friendly_names` gives them
+    display_names: HashMap<String, String>,
+    /// Timer for noting where windows are, for `restore_on_reconnect`
+    places_timer: Option<usize>,
+    /// Layouts last applied to windows, with the rect each ended up at
+    layouts: IntMap<isize, (Layout, BindOptions, RECT)>,
+    /// Where each window was when last noted, per set of monitors
+    places: HashMap<u64, IntMap<isize, Placement>>,
+    /// Waits for monitors to settle before windows are restored
+    restore_timer: Option<usize>,
+    /// Window rects from before `MaximizeToggle` maximized them
+    unmaximized: IntMap<isize, RECT>,
+    /// Where windows were before recent moves, latest last
+    history: IntMap<isize, Vec<Placement>>,
+    /// Where windows were before their first move
+    originals: IntMap<isize, Placement>,
+    /// Columns filled by `PushColumn`
+    zones: Vec<Zone>,
+    /// Windows hidden by `Scratchpad`, per lowercased `exe`
+    scratchpads: HashMap<String, HWND>,
+    /// Windows minimized by `ToggleDesktop`, in z-order
+    desktop_hidden: Vec<HWND>,
+    /// Styles and placements of windows made borderless by `ToggleBorderless`
+    borderless: IntMap<isize, (WINDOW_STYLE, Placement)>,
+    /// Full heights of windows rolled up by `ToggleShade`
+    shaded: IntMap<isize, i32>,
+}
+
+/// A chord leader has been pressed and its follow-up keys are registered
+#[derive(Debug)]
+struct ActiveChord {
+    keybind: usize,
+    timer: usize,
+}
+
+/// A window hidden by `SendToDesktop`, waiting for the desktop switch
+#[derive(Debug)]
+struct PendingDesktopMove {
+    timer: usize,
+    hwnd: HWND,
+    /// Desktop to switch back to once the window is shown, unless following it
+    return_to: Option<usize>,
+}
+
+/// A window asked to close by `ForceClose`, given until the timer to do so
+#[derive(Debug)]
+struct PendingClose {
+    timer: usize,
+    hwnd: HWND,
+    pid: u32,
+}
+
+/// Auto-hide was toggled and the work areas are about to change
+#[derive(Debug)]
+struct PendingWorkArea {
+    timer: usize,
+    /// Monitors as they were before
+    before: Vec<MONITORINFO>,
+}
+
+#[derive(Debug)]
+struct PendingMacro {
+    timer: usize,
+    steps: Vec<Action>,
+    options: BindOptions,
+}
+
+#[derive(Debug, Clone, Copy)]
+struct CycleState {
+    keybind: usize,
+    position: usize,
+}
+
+/// A layout rect that several windows share, split into equal rows
+#[derive(Debug)]
+struct Zone {
+    rect: (i32, i32, i32, i32),
+    mi: MONITORINFO,
+    /// Occupants from top to bottom
+    windows: Vec<HWND>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+struct Placement {
+    rect: RECT,
+    maximized: bool,
 }
 
 impl DefaultLayout {
-    /// Calculate (x, y, w, h)
-    fn calc(self, margin: u8, mi: &MONITORINFO) -> (i32, i32, i32, i32) {
+    /// Every layout that takes no parameter
+    const ALL: [DefaultLayout; 19] = [
+        DefaultLayout::LeftHalf,
+        DefaultLayout::RightHalf,
+        DefaultLayout::LeftThird,
+        DefaultLayout::RightThird,
+        DefaultLayout::LeftTwoThirds,
+        DefaultLayout::RightTwoThirds,
+        DefaultLayout::CenterThird,
+        DefaultLayout::CenterSmall,
+        DefaultLayout::CenterMedium,
+        DefaultLayout::CenterLarge,
+        DefaultLayout::TopLeft,
+        DefaultLayout::TopRight,
+        DefaultLayout::BottomRight,
+        DefaultLayout::BottomLeft,
+        DefaultLayout::TopHalf,
+        DefaultLayout::BottomHalf,
+        DefaultLayout::TopThird,
+        DefaultLayout::MiddleThird,
+        DefaultLayout::BottomThird,
+    ];
+
+    /// Calculate (x, y, w, h). `ratio` overrides the share of the work area
+    /// covered by half and two-thirds layouts.
+    fn calc(self, margin: u8, ratio: Option<f32>, mi: &MONITORINFO) -> (i32, i32, i32, i32) {
         let m = margin as i32;
 
         let work_left = mi.rcWork.left;
@@ -202,15 +2123,20 @@ impl DefaultLayout {
         let inner_width = (work_width - 2 * m).max(0);
         let inner_height = (work_height - 2 * m).max(0);
 
+        let share = |size: i32, num: i32, den: i32| match ratio {
+            Some(ratio) => (size as f32 * ratio.clamp(0.0, 1.0)) as i32,
+            None => size * num / den,
+        };
+
         match self {
             DefaultLayout::LeftHalf => {
-                let w = inner_width / 2;
+                let w = share(inner_width, 1, 2);
                 let x = work_left + m;
                 let y = work_top + m;
                 (x, y, w, inner_height)
             }
             DefaultLayout::RightHalf => {
-                let w = inner_width / 2;
+                let w = share(inner_width, 1, 2);
                 let x = work_left + m + (inner_width - w);
                 let y = work_top + m;
                 (x, y, w, inner_height)
@@ -228,13 +2154,13 @@ impl DefaultLayout {
                 (x, y, w, inner_height)
             }
             DefaultLayout::LeftTwoThirds => {
-                let w = inner_width * 2 / 3;
+                let w = share(inner_width, 2, 3);
                 let x = work_left + m;
                 let y = work_top + m;
                 (x, y, w, inner_height)
             }
             DefaultLayout::RightTwoThirds => {
-                let w = inner_width * 2 / 3;
+                let w = share(inner_width, 2, 3);
                 let x = work_left + m + (inner_width - w);
                 let y = work_top + m;
                 (x, y, w, inner_height)
@@ -266,15 +2192,154 @@ impl DefaultLayout {
                 let y = work_top + m;
                 (x, y, w, h)
             }
+            DefaultLayout::TopLeft
+            | DefaultLayout::TopRight
+            | DefaultLayout::BottomRight
+            | DefaultLayout::BottomLeft => {
+                let w = inner_width / 2;
+                let h = inner_height / 2;
+                let left = matches!(self, DefaultLayout::TopLeft | DefaultLayout::BottomLeft);
+                let top = matches!(self, DefaultLayout::TopLeft | DefaultLayout::TopRight);
+                let x = work_left + m + if left { 0 } else { inner_width - w };
+                let y = work_top + m + if top { 0 } else { inner_height - h };
+                (x, y, w, h)
+            }
+            DefaultLayout::TopHalf => {
+                let h = share(inner_height, 1, 2);
+                let x = work_left + m;
+                let y = work_top + m;
+                (x, y, inner_width, h)
+            }
+            DefaultLayout::BottomHalf => {
+                let h = share(inner_height, 1, 2);
+                let x = work_left + m;
+                let y = work_top + m + (inner_height - h);
+                (x, y, inner_width, h)
+            }
+            DefaultLayout::TopThird => {
+                let h = inner_height / 3;
+                let x = work_left + m;
+                let y = work_top + m;
+                (x, y, inner_width, h)
+            }
+            DefaultLayout::MiddleThird => {
+                let h = inner_height / 3;
+                let x = work_left + m;
+                let y = work_top + m + h;
+                (x, y, inner_width, h)
+            }
+            DefaultLayout::BottomThird => {
+                let h = inner_height / 3;
+                let x = work_left + m;
+                let y = work_top + m + 2 * h;
+                (x, y, inner_width, h)
+            }
+            DefaultLayout::CenterPercent(percent) => {
+                let p = percent.min(100) as i32;
+                let w = inner_width * p / 100;
+                let h = inner_height * p / 100;
+                let x = work_left + (work_width - w) / 2;
+                let y = work_top + (work_height - h) / 2;
+                (x, y, w, h)
+            }
         }
     }
+
+    /// Share of the work area covered by a half layout, given the configured
+    /// `split_ratio`
+    fn split_share(self, split_ratio: Option<f32>) -> Option<f32> {
+        let split = split_ratio?.clamp(0.0, 1.0);
+
+        match self {
+            DefaultLayout::LeftHalf | DefaultLayout::TopHalf => Some(split),
+            DefaultLayout::RightHalf | DefaultLayout::BottomHalf => Some(1.0 - split),
+            _ => None,
+        }
+    }
+
+    /// The next corner clockwise, for corner layouts
+    fn next_corner(self) -> Option<Self> {
+        match self {
+            DefaultLayout::TopLeft => Some(DefaultLayout::TopRight),
+            DefaultLayout::TopRight => Some(DefaultLayout::BottomRight),
+            DefaultLayout::BottomRight => Some(DefaultLayout::BottomLeft),
+            DefaultLayout::BottomLeft => Some(DefaultLayout::TopLeft),
+            _ => None,
+        }
+    }
+}
+
+impl GridLayout {
+    /// Calculate (x, y, w, h). Cells that don't fit are clamped to the grid.
+    fn calc(self, margin: u8, mi: &MONITORINFO) -> (i32, i32, i32, i32) {
+        let m = margin as i32;
+
+        let inner_width = (mi.rcWork.right - mi.rcWork.left - 2 * m).max(0);
+        let inner_height = (mi.rcWork.bottom - mi.rcWork.top - 2 * m).max(0);
+
+        // Edge of the n-th of `count` tracks, so rounding never leaves gaps
+        let edge = |n: u32, count: u32, size: i32| {
+            (n.min(count) as i64 * size as i64 / count as i64) as i32
+        };
+
+        let cols = self.cols.max(1);
+        let rows = self.rows.max(1);
+        let col = self.col.min(cols - 1);
+        let row = self.row.min(rows - 1);
+
+        let left = edge(col, cols, inner_width);
+        let right = edge(col.saturating_add(self.col_span.max(1)), cols, inner_width);
+        let top = edge(row, rows, inner_height);
+        let bottom = edge(row.saturating_add(self.row_span.max(1)), rows, inner_height);
+
+        (
+            mi.rcWork.left + m + left,
+            mi.rcWork.top + m + top,
+            right - left,
+            bottom - top,
+        )
+    }
 }
 
 impl KeyBindRegistry {
-    fn new(cfg: Config) -> Self {
+    fn new(cfg: Config, config_path: PathBuf, profile: Option<String>) -> Self {
         let mut this = Self {
+            config_modified: modified(&config_path),
+            reload_timer: None,
+            config_path,
             map: IntMap::with_capacity_and_hasher(cfg.keybinds.len(), BuildNoHashHasher::default()),
+            keybinds: cfg.keybinds.clone(),
+            layer: None,
             cfg,
+            profile,
+            problems: Vec::new(),
+            failures: IntMap::default(),
+            started: Instant::now(),
+            recent: VecDeque::with_capacity(RECENT_ACTIONS),
+            chord: None,
+            resizing: false,
+            paused: false,
+            disabled: HashSet::new(),
+            cycles: IntMap::default(),
+            tags: IntMap::default(),
+            macros: Vec::new(),
+            desktop_moves: Vec::new(),
+            closing: Vec::new(),
+            work_area_change: None,
+            displays: Vec::new(),
+            display_names: HashMap::new(),
+            places_timer: None,
+            layouts: IntMap::default(),
+            places: HashMap::new(),
+            restore_timer: None,
+            unmaximized: IntMap::default(),
+            history: IntMap::default(),
+            originals: IntMap::default(),
+            zones: Vec::new(),
+            scratchpads: HashMap::new(),
+            desktop_hidden: Vec::new(),
+            borderless: IntMap::default(),
+            shaded: IntMap::default(),
         };
 
         this.register();
@@ -283,86 +2348,2820 @@ impl KeyBindRegistry {
 
     fn register(&mut self) {
         let mut buf = String::new();
+        let mut combos: HashMap<(u32, u16), usize> = HashMap::new();
+
+        self.problems = find_conflicts(&self.cfg.keybinds);
+        self.failures.clear();
+
+        if let Some(layer) = &self.layer {
+            let conflicts = find_conflicts(&self.cfg.layers[layer]);
+            let conflicts = conflicts.into_iter().map(|c| format!("Layer {layer}: {c}"));
+            self.problems.extend(conflicts);
+        }
+
+        for (index, keybind) in self.keybinds.iter().enumerate() {
+            // Only the keybinds that can resume stay registered while paused
+            if self.paused && !matches!(keybind.action, Some(Action::TogglePause)) {
+                continue;
+            }
+
+            if keybind
+                .name
+                .as_ref()
+                .is_some_and(|name| self.disabled.contains(name))
+            {
+                continue;
+            }
+
+            let (mut mods, key) = match keybind.parse_combo() {
+                Ok(combo) => combo,
+                Err(err) => {
+                    self.problems.push(format!(
+                        "Failed to register keybind {index} ({}): {err}",
+                        keybind.combo()
+                    ));
+                    self.failures.insert(index, err);
+                    continue;
+                }
+            };
+
+            let combo = (mods.0, key.0);
+
+            for (offset, bind) in keybind.chord.iter().enumerate() {
+                if let Err(err) = parse_combo(&bind.modifiers, &bind.key) {
+                    self.problems.push(format!(
+                        "Failed to register chord key {offset} ({}) of keybind {index}: {err}",
+                        describe_combo(&bind.modifiers, &bind.key)
+                    ));
+                }
+            }
+
+            if let Some(id) = combos.get(&combo) {
+                self.map.entry(*id).or_default().push(index);
+                continue;
+            }
 
-        for (index, keybind) in self.cfg.keybinds.iter().enumerate() {
             buf.clear();
 
             unsafe {
-                write!(buf, "winmgr_bind_{index}").unwrap();
+                write!(buf, "winmgr_bind_{index}\0").unwrap();
 
                 let id = GlobalAddAtomA(PCSTR::from_raw(buf.as_ptr()));
 
-                let mods = keybind
-                    .modifiers
-                    .iter()
-                    .fold(HOT_KEY_MODIFIERS(0), |mut acc, m| {
-                        acc |= m.into();
-                        acc
-                    });
-
-                let key: VIRTUAL_KEY = (&keybind.key).into();
+                if !keybind.allow_repeat {
+                    mods |= MOD_NOREPEAT;
+                }
 
-                if let Err(err) = RegisterHotKey(None, id.into(), mods | MOD_NOREPEAT, key.0.into())
-                {
-                    eprintln!("Failed to register keybind {buf}: {err}");
+                if let Err(err) = RegisterHotKey(None, id.into(), mods, key.0.into()) {
+                    self.problems.push(format!(
+                        "Failed to register keybind {index} ({}): {err}",
+                        keybind.combo()
+                    ));
+                    self.failures.insert(index, err.to_string());
                     continue;
                 }
 
-                self.map.insert(id.into(), index);
+                combos.insert(combo, id.into());
+                self.map.insert(id.into(), vec![index]);
+            }
+        }
+
+        for (index, bind) in self.cfg.mousebinds.iter().enumerate() {
+            if let Err(err) = parse_modifiers(&bind.modifiers) {
+                self.problems
+                    .push(format!("Failed to register mouse binding {index}: {err}"));
             }
         }
+
+        for (index, gesture) in self.cfg.gestures.iter().enumerate() {
+            if gesture.double_tap.parse().is_none() {
+                self.problems.push(format!(
+                    "Failed to register gesture {index}: invalid modifier \"{}\"",
+                    gesture.double_tap.0
+                ));
+            }
+        }
+
+        let mousebinds = self
+            .cfg
+            .mousebinds
+            .iter()
+            .filter(|_| !self.paused)
+            .map(|bind| {
+                parse_modifiers(&bind.modifiers)
+                    .ok()
+                    .map(|mods| (mods, bind.button))
+            })
+            .collect();
+
+        let gestures = self
+            .cfg
+            .gestures
+            .iter()
+            .filter(|_| !self.paused)
+            .map(|gesture| gesture.double_tap.parse())
+            .collect();
+
+        unsafe {
+            hooks::set_mouse_binds(mousebinds);
+            hooks::set_gestures(gestures, self.cfg.double_tap_ms);
+        }
     }
 
-    fn run(&self) {
+    fn run(&mut self) {
         unsafe {
+            if let Err(err) = system::create_window() {
+                eprintln!("Failed to create notification window: {err}");
+            }
+
+            watch::watch_config(&self.config_path);
+            ipc::serve();
+            hooks::watch_windows();
+
+            self.displays = monitor_infos();
+            self.display_names = display::friendly_names();
+            self.places_timer = Some(SetTimer(None, 0, PLACES_POLL_MS, None));
+
             let mut msg: MSG = MSG::default();
 
             while GetMessageW(&mut msg, None, 0, 0).as_bool() {
-                if msg.message == WM_QUIT {
-                    break;
+                match msg.message {
+                    WM_QUIT => break,
+                    WM_HOTKEY => self.on_hotkey(msg.wParam.0),
+                    WM_TIMER => self.on_timer(msg.wParam.0),
+                    hooks::WM_APP_MOUSEBIND => self.on_mousebind(msg.wParam.0),
+                    hooks::WM_APP_GESTURE => self.on_gesture(msg.wParam.0),
+                    hooks::WM_APP_WINDOW_SHOWN => {
+                        self.on_window_shown(HWND(msg.wParam.0 as *mut _))
+                    }
+                    hooks::WM_APP_WINDOW_DESTROYED => {
+                        self.forget_window(HWND(msg.wParam.0 as *mut _))
+                    }
+                    system::WM_APP_RESUMED => self.reregister(),
+                    system::WM_APP_DISPLAY_CHANGED => self.on_display_change(),
+                    system::WM_APP_WORK_AREA_CHANGED => {
+                        if self.cfg.follow_work_area {
+                            self.reapply_layouts();
+                        }
+                    }
+                    ipc::WM_APP_IPC => self.on_ipc(ipc::take(msg.lParam)),
+                    watch::WM_APP_CONFIG_CHANGED => self.on_config_changed(),
+                    _ => {
+                        // Turns key presses into characters for the switcher
+                        let _ = TranslateMessage(&msg);
+                        DispatchMessageW(&msg);
+                    }
                 }
+            }
+        }
+    }
 
-                if msg.message == WM_HOTKEY {
-                    let hotkey_id = msg.wParam.0;
-
-                    let Some(idx) = self.map.get(&hotkey_id) else {
-                        eprintln!("Hotkey {hotkey_id} is not registered");
-                        continue;
-                    };
+    unsafe fn on_ipc(&mut self, pending: ipc::Pending) {
+        let response = match &pending.request {
+            ipc::Request::ApplyLayout {
+                layout,
+                hwnd,
+                window,
+            } => {
+                let action = Action::ApplyLayout(*layout);
+                let options = BindOptions::default();
 
-                    let kb = &self.cfg.keybinds[*idx];
+                match (hwnd, window) {
+                    (None, None) => {
+                        self.dispatch(&action, &options);
+                        ipc::Response::ok()
+                    }
+                    (Some(hwnd), _) => {
+                        let hwnd = HWND(*hwnd as *mut _);
 
-                    let hwnd: HWND = GetForegroundWindow();
+                        match IsWindow(Some(hwnd)).as_bool() {
+                            true => {
+                                self.dispatch_to(hwnd, &action, &options);
+                                ipc::Response::ok()
+                            }
+                            false => ipc::Response::error("There is no such window"),
+                        }
+                    }
+                    (None, Some(window)) => {
+                        match window::app_windows()
+                            .into_iter()
+                            .find(|&hwnd| window.matches(hwnd))
+                        {
+                            Some(hwnd) => {
+                                self.dispatch_to(hwnd, &action, &options);
+                                ipc::Response::ok()
+                            }
+                            None => ipc::Response::error("No window matches"),
+                        }
+                    }
+                }
+            }
+            ipc::Request::Reload => match self.reload() {
+                true => ipc::Response::ok(),
+                false => ipc::Response::error("The config could not be read"),
+            },
+            ipc::Request::Pause => {
+                self.set_paused(true);
+                ipc::Response::ok()
+            }
+            ipc::Request::Resume => {
+                self.set_paused(false);
+                ipc::Response::ok()
+            }
+            ipc::Request::SwitchProfile { name } => match self.switch_profile(name) {
+                true => ipc::Response::ok(),
+                false => ipc::Response::error(format!("Could not switch to profile {name}")),
+            },
+            ipc::Request::ToggleKeybinds { name, enabled } => {
+                let named = self
+                    .cfg
+                    .keybinds
+                    .iter()
+                    .chain(self.cfg.layers.values().flatten())
+                    .any(|keybind| keybind.name.as_deref() == Some(name.as_str()));
 
-                    if hwnd.is_invalid() {
-                        // No active window
-                        continue;
+                match named {
+                    true => {
+                        let enabled = enabled.unwrap_or(self.disabled.contains(name));
+                        self.set_keybinds_enabled(name, enabled);
+                        ipc::Response::ok()
                     }
+                    false => ipc::Response::error(format!("No keybind is named {name}")),
+                }
+            }
+            ipc::Request::QueryState => ipc::Response {
+                state: Some(self.state()),
+                ..ipc::Response::ok()
+            },
+            ipc::Request::QueryKeys => ipc::Response {
+                keys: Some(self.keys()),
+                ..ipc::Response::ok()
+            },
+            ipc::Request::Quit => {
+                pending.reply(ipc::Response::ok());
+                self.shutdown();
+                return;
+            }
+        };
 
-                    let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+        pending.reply(response);
+    }
 
-                    let mut mi = MONITORINFO {
-                        cbSize: size_of::<MONITORINFO>() as u32,
-                        ..Default::default()
-                    };
+    fn state(&self) -> ipc::State {
+        let recent_actions = self
+            .recent
+            .iter()
+            .map(|(time, action)| ipc::RecentAction {
+                action: serde_json::to_string(action).unwrap_or_default(),
+                secs_ago: time.elapsed().as_secs(),
+            })
+            .collect();
 
-                    if !GetMonitorInfoW(monitor, &mut mi).as_bool() {
-                        // Could not query monitor info
-                        eprintln!("Could not query monitor info");
-                        continue;
-                    }
+        let mut disabled: Vec<String> = self.disabled.iter().cloned().collect();
+        disabled.sort();
 
-                    let (x, y, w, h) = match kb.layout {
-                        Layout::Custom(layout) => (layout.x, layout.y, layout.w, layout.h),
-                        Layout::Default(layout) => layout.calc(self.cfg.margin, &mi),
-                    };
+        ipc::State {
+            config: self.config_path.clone(),
+            profile: self.profile.clone(),
+            paused: self.paused,
+            layer: self.layer.clone(),
+            disabled,
+            keybinds: self.keybinds.len(),
+            registered: self.map.len(),
+            failed: self.failures.len(),
+            uptime_secs: self.started.elapsed().as_secs(),
+            recent_actions,
+        }
+    }
+
+    /// The keybinds in effect and whether each one is registered
+    fn keys(&self) -> Vec<ipc::KeyInfo> {
+        let layer_keybinds = match &self.layer {
+            Some(layer) => self.cfg.layers[layer].len(),
+            None => 0,
+        };
 
-                    let flags: SET_WINDOW_POS_FLAGS =
-                        SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED;
+        let registered: HashSet<usize> = self.map.values().flatten().copied().collect();
 
-                    SetWindowPos(hwnd, None, x, y, w, h, flags).unwrap();
-                }
+        self.keybinds
+            .iter()
+            .enumerate()
+            .map(|(index, keybind)| {
+                let layer = self.layer.as_deref().filter(|_| index < layer_keybinds);
+
+                let (status, error) = if registered.contains(&index) {
+                    (ipc::KeyStatus::Registered, None)
+                } else if let Some(err) = self.failures.get(&index) {
+                    (ipc::KeyStatus::Failed, Some(err.clone()))
+                } else if keybind
+                    .name
+                    .as_ref()
+                    .is_some_and(|name| self.disabled.contains(name))
+                {
+                    (ipc::KeyStatus::Disabled, None)
+                } else {
+                    (ipc::KeyStatus::Paused, None)
+                };
+
+                keybind.info(layer, status, error)
+            })
+            .collect()
+    }
+
+    /// Excluded windows are checked per action, so that leaving resize mode
+    /// and chords, and actions that don't touch a window, work over them too
+    unsafe fn on_hotkey(&mut self, hotkey_id: usize) {
+        if self.resizing
+            && let Some(offset) = hotkey_id.checked_sub(RESIZE_HOTKEY_BASE as usize)
+            && offset < RESIZE_KEYS.len() + RESIZE_EXIT_KEYS.len()
+        {
+            self.on_resize_key(offset);
+            return;
+        }
+
+        // Any hotkey press ends an active chord, whether it completes it or not
+        if let Some(chord) = self.chord.take() {
+            self.exit_chord(&chord);
+
+            if hotkey_id == CHORD_CANCEL_HOTKEY as usize {
+                return;
+            }
+
+            if let Some(offset) = hotkey_id.checked_sub(CHORD_HOTKEY_BASE as usize)
+                && let Some(bind) = self.keybinds[chord.keybind].chord.get(offset)
+            {
+                let action = bind.action.clone();
+                let options = self.keybinds[chord.keybind].options.clone();
+                self.dispatch(&action, &options);
+                return;
+            }
+        }
+
+        let Some(indexes) = self.map.get(&hotkey_id) else {
+            eprintln!("Hotkey {hotkey_id} is not registered");
+            return;
+        };
+
+        let Some(idx) = self.select_keybind(indexes) else {
+            // No keybind for this combo applies to the focused application
+            return;
+        };
+
+        let kb = &self.keybinds[idx];
+
+        if !kb.chord.is_empty() {
+            self.enter_chord(idx);
+            return;
+        }
+
+        if !kb.cycle_layouts().is_empty() {
+            self.cycle(idx);
+            return;
+        }
+
+        if let Some(action) = kb.action.clone() {
+            let options = kb.options.clone();
+            self.dispatch(&action, &options);
+        }
+    }
+
+    /// Whether the window is one winmgr should leave alone
+    unsafe fn excluded(&self, hwnd: HWND) -> bool {
+        self.cfg
+            .exclusions
+            .iter()
+            .any(|exclusion| exclusion.matches(hwnd))
+            || self
+                .cfg
+                .rules
+                .iter()
+                .any(|rule| rule.exclude && rule.window.matches(hwnd))
+    }
+
+    /// Whether arranging the windows on a monitor should leave the window be
+    unsafe fn floating(&self, hwnd: HWND) -> bool {
+        self.excluded(hwnd)
+            || self
+                .cfg
+                .rules
+                .iter()
+                .any(|rule| rule.float && rule.window.matches(hwnd))
+    }
+
+    /// A setting from the first of the window's rules that sets it
+    unsafe fn rule<T>(&self, hwnd: HWND, setting: impl Fn(&Rule) -> Option<T>) -> Option<T> {
+        self.cfg
+            .rules
+            .iter()
+            .filter(|rule| rule.window.matches(hwnd))
+            .find_map(setting)
+    }
+
+    /// The options, with the monitor the window's rules set if they name none
+    unsafe fn ruled_options<'a>(
+        &self,
+        hwnd: HWND,
+        options: &'a BindOptions,
+    ) -> Cow<'a, BindOptions> {
+        if options.monitor.is_some() {
+            return Cow::Borrowed(options);
+        }
+
+        match self.rule(hwnd, |rule| rule.monitor.clone()) {
+            Some(monitor) => Cow::Owned(BindOptions {
+                monitor: Some(monitor),
+                ..options.clone()
+            }),
+            None => Cow::Borrowed(options),
+        }
+    }
+
+    /// Windows on the same monitor as `hwnd` to arrange along with it
+    unsafe fn tiled_windows(&self, hwnd: HWND) -> Vec<HWND> {
+        window::same_monitor(hwnd)
+            .into_iter()
+            .filter(|&other| other == hwnd || !self.floating(other))
+            .collect()
+    }
+
+    unsafe fn apply_rules(&mut self, hwnd: HWND, options: &BindOptions) {
+        if let Some(layout) = self.rule(hwnd, |rule| rule.layout) {
+            self.remember(hwnd);
+            self.leave_zone(hwnd);
+            self.cycles.remove(&(hwnd.0 as isize));
+            self.apply_layout(hwnd, layout, options);
+            self.note_layout(hwnd, layout, options);
+        }
+
+        if self
+            .rule(hwnd, |rule| rule.always_on_top.then_some(()))
+            .is_some()
+        {
+            set_always_on_top(hwnd, true);
+        }
+
+        if let Some(percent) = self.rule(hwnd, |rule| rule.opacity) {
+            set_opacity(hwnd, percent);
+        }
+    }
+
+    /// Apply the rules for a window that has just opened
+    unsafe fn on_window_shown(&mut self, hwnd: HWND) {
+        if self.paused || !IsWindow(Some(hwnd)).as_bool() || self.excluded(hwnd) {
+            return;
+        }
+
+        let options = BindOptions::default();
+        let options = self.ruled_options(hwnd, &options);
+
+        self.apply_rules(hwnd, &options);
+    }
+
+    /// Drop everything kept about a window that has closed, so none of it
+    /// carries over to a new window given the same handle
+    unsafe fn forget_window(&mut self, hwnd: HWND) {
+        let key = hwnd.0 as isize;
+
+        self.leave_zone(hwnd);
+        self.cycles.remove(&key);
+        self.tags.retain(|_, &mut tagged| tagged != hwnd);
+        self.layouts.remove(&key);
+        self.unmaximized.remove(&key);
+        self.history.remove(&key);
+        self.originals.remove(&key);
+        self.scratchpads
+            .retain(|_, &mut scratchpad| scratchpad != hwnd);
+        self.desktop_hidden.retain(|&hidden| hidden != hwnd);
+        self.borderless.remove(&key);
+        self.shaded.remove(&key);
+
+        for places in self.places.values_mut() {
+            places.remove(&key);
+        }
+    }
+
+    /// Pick the keybind to run among those sharing a combo: one whose `when`
+    /// clause matches the foreground window, falling back to an unconditional one
+    unsafe fn select_keybind(&self, indexes: &[usize]) -> Option<usize> {
+        let hwnd: HWND = GetForegroundWindow();
+
+        let when = |idx: &usize| self.keybinds[*idx].when.as_ref();
+
+        indexes
+            .iter()
+            .find(|idx| when(idx).is_some_and(|when| !hwnd.is_invalid() && when.matches(hwnd)))
+            .or_else(|| indexes.iter().find(|idx| when(idx).is_none()))
+            .copied()
+    }
+
+    unsafe fn maximize_toggle(&mut self, hwnd: HWND) {
+        let key = hwnd.0 as isize;
+
+        if !IsZoomed(hwnd).as_bool() {
+            if let Some(rect) = window::rect(hwnd) {
+                self.unmaximized.insert(key, rect);
+            }
+
+            let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+            return;
+        }
+
+        let _ = ShowWindow(hwnd, SW_RESTORE);
+
+        // The restore position Windows keeps is wrong for windows snapped
+        // before maximizing, so put it back where it actually was
+        if let Some(rect) = self.unmaximized.remove(&key) {
+            let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE;
+
+            let _ = SetWindowPos(
+                hwnd,
+                None,
+                rect.left,
+                rect.top,
+                rect.right - rect.left,
+                rect.bottom - rect.top,
+                flags,
+            );
+        }
+    }
+
+    unsafe fn jump_to_tag(&mut self, slot: u8) {
+        let Some(&hwnd) = self.tags.get(&slot) else {
+            return;
+        };
+
+        // The tagged window has since been destroyed
+        if !IsWindow(Some(hwnd)).as_bool() {
+            self.tags.remove(&slot);
+            return;
+        }
+
+        focus_window(hwnd);
+    }
+
+    /// Move a window to desktop `n`, counting from 1. Without Explorer's
+    /// internal interface, the window is hidden now and shown on the other
+    /// desktop once the switch there has happened.
+    unsafe fn send_to_desktop(&mut self, hwnd: HWND, n: u32, follow: bool) {
+        let Some(index) = n.checked_sub(1).map(|index| index as usize) else {
+            return;
+        };
+
+        let (Some(current), Some(target)) =
+            (desktop::current(), desktop::desktops().get(index).copied())
+        else {
+            return;
+        };
+
+        if desktop::window_desktop(hwnd) == Some(target) {
+            if follow {
+                desktop::switch_to(index);
+            }
+
+            return;
+        }
+
+        if desktop::move_to(hwnd, index) {
+            if follow {
+                desktop::switch_to(index);
+                focus_window(hwnd);
+            }
+
+            return;
+        }
+
+        let _ = ShowWindow(hwnd, SW_HIDE);
+
+        if !desktop::switch_to(index) {
+            let _ = ShowWindow(hwnd, SW_SHOW);
+            return;
+        }
+
+        self.desktop_moves.push(PendingDesktopMove {
+            timer: SetTimer(None, 0, DESKTOP_SWITCH_MS, None),
+            hwnd,
+            return_to: (!follow).then_some(current),
+        });
+    }
+
+    unsafe fn toggle_desktop(&mut self) {
+        if self.desktop_hidden.is_empty() {
+            self.desktop_hidden = window::app_windows()
+                .into_iter()
+                .filter(|&hwnd| !IsIconic(hwnd).as_bool() && !self.excluded(hwnd))
+                .collect();
+
+            for &hwnd in &self.desktop_hidden {
+                let _ = ShowWindow(hwnd, SW_SHOWMINNOACTIVE);
+            }
+
+            return;
+        }
+
+        // Bottom of the z-order first, so that the windows stack up as they were
+        let windows: Vec<HWND> = self
+            .desktop_hidden
+            .drain(..)
+            .filter(|&hwnd| IsWindow(Some(hwnd)).as_bool() && IsIconic(hwnd).as_bool())
+            .collect();
+
+        for &hwnd in windows.iter().rev() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        if let Some(&top) = windows.first() {
+            focus_window(top);
+        }
+    }
+
+    unsafe fn toggle_borderless(&mut self, hwnd: HWND) {
+        if let Some((style, placement)) = self.borderless.remove(&(hwnd.0 as isize)) {
+            set_style(hwnd, style);
+            restore_placement(hwnd, placement);
+            return;
+        }
+
+        let (Some(mi), Some(rect)) = (monitor_info(hwnd), window::rect(hwnd)) else {
+            return;
+        };
+
+        let style = WINDOW_STYLE(GetWindowLongPtrW(hwnd, GWL_STYLE) as u32);
+        let placement = Placement {
+            rect,
+            maximized: IsZoomed(hwnd).as_bool(),
+        };
+
+        if placement.maximized {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        self.borderless.insert(hwnd.0 as isize, (style, placement));
+
+        set_style(hwnd, style & !(WS_CAPTION | WS_THICKFRAME));
+
+        // In front of the taskbar, which then gets out of the way
+        let r = mi.rcMonitor;
+        let flags: SET_WINDOW_POS_FLAGS = SWP_FRAMECHANGED | SWP_NOACTIVATE;
+
+        let _ = SetWindowPos(
+            hwnd,
+            Some(HWND_TOP),
+            r.left,
+            r.top,
+            r.right - r.left,
+            r.bottom - r.top,
+            flags,
+        );
+    }
+
+    unsafe fn force_close(&mut self, hwnd: HWND) {
+        let Some(pid) = window::process_id(hwnd) else {
+            return;
+        };
+
+        let _ = PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0));
+
+        if pid == GetCurrentProcessId() || self.closing.iter().any(|c| c.hwnd == hwnd) {
+            return;
+        }
+
+        self.closing.push(PendingClose {
+            timer: SetTimer(None, 0, FORCE_CLOSE_MS, None),
+            hwnd,
+            pid,
+        });
+    }
+
+    unsafe fn toggle_taskbar_autohide(&mut self) {
+        // Pressed again before the last change settled, which is the one to
+        // compare with
+        let before = match self.work_area_change.take() {
+            Some(pending) => {
+                let _ = KillTimer(None, pending.timer);
+                pending.before
+            }
+            None => monitor_infos(),
+        };
+
+        if !toggle_taskbar_autohide() {
+            return;
+        }
+
+        self.work_area_change = Some(PendingWorkArea {
+            timer: SetTimer(None, 0, TASKBAR_SETTLE_MS, None),
+            before,
+        });
+    }
+
+    unsafe fn on_display_change(&mut self) {
+        self.displays = monitor_infos();
+        self.display_names = display::friendly_names();
+
+        let missing = validate::screen_problems(&self.cfg, &self.displays, &self.display_names);
+
+        if !missing.is_empty() {
+            report::log(&missing);
+        }
+
+        if !self.cfg.restore_on_reconnect {
+            return;
+        }
+
+        // Restarted by each change of a burst, so windows are restored once
+        // Windows is done moving them
+        if let Some(timer) = self.restore_timer.take() {
+            let _ = KillTimer(None, timer);
+        }
+
+        self.restore_timer = Some(SetTimer(None, 0, DISPLAY_SETTLE_MS, None));
+    }
+
+    /// Put windows back where they were the last time the monitors connected
+    /// now were
+    unsafe fn restore_places(&mut self) {
+        let Some(places) = self.places.get(&topology(&self.displays)) else {
+            return;
+        };
+
+        for (&hwnd, &placement) in places {
+            let hwnd = HWND(hwnd as *mut _);
+
+            if IsWindow(Some(hwnd)).as_bool() && !IsIconic(hwnd).as_bool() {
+                restore_placement(hwnd, placement);
+            }
+        }
+    }
+
+    /// Note where every window is on the monitors connected now, to put it
+    /// back when they're connected again after a change
+    unsafe fn note_places(&mut self) {
+        // Windows moves windows about while monitors change, which is no place
+        // to remember. The places are noted again once the change is handled.
+        let displays = monitor_infos();
+
+        if self.restore_timer.is_some() || topology(&displays) != topology(&self.displays) {
+            return;
+        }
+
+        let places = self.places.entry(topology(&displays)).or_default();
+
+        places.retain(|&hwnd, _| IsWindow(Some(HWND(hwnd as *mut _))).as_bool());
+
+        // Minimized windows keep the place they were last seen at
+        for hwnd in window::app_windows() {
+            if !IsIconic(hwnd).as_bool()
+                && let Some(rect) = window::rect(hwnd)
+            {
+                let placement = Placement {
+                    rect,
+                    maximized: IsZoomed(hwnd).as_bool(),
+                };
+
+                places.insert(hwnd.0 as isize, placement);
+            }
+        }
+    }
+
+    unsafe fn note_layout(&mut self, hwnd: HWND, layout: Layout, options: &BindOptions) {
+        if let Some(rect) = window::rect(hwnd) {
+            self.layouts
+                .insert(hwnd.0 as isize, (layout, options.clone(), rect));
+        }
+    }
+
+    /// Apply layouts again to the windows still where their layout put them,
+    /// for a changed work area. Returns the windows the layouts are kept for.
+    unsafe fn reapply_layouts(&mut self) -> HashSet<isize> {
+        self.layouts.retain(|&hwnd, (_, _, rect)| {
+            let hwnd = HWND(hwnd as *mut _);
+
+            // Moved by other means since, so no longer the layout's to place
+            IsWindow(Some(hwnd)).as_bool() && window::rect(hwnd) == Some(*rect)
+        });
+
+        let layouts: Vec<(isize, Layout, BindOptions)> = self
+            .layouts
+            .iter()
+            .map(|(&hwnd, (layout, options, _))| (hwnd, *layout, options.clone()))
+            .collect();
+
+        for (hwnd, layout, options) in &layouts {
+            let hwnd = HWND(*hwnd as *mut _);
+
+            if !IsIconic(hwnd).as_bool() && !IsZoomed(hwnd).as_bool() {
+                self.apply_layout(hwnd, *layout, options);
+                self.note_layout(hwnd, *layout, options);
             }
         }
+
+        layouts.into_iter().map(|(hwnd, _, _)| hwnd).collect()
     }
+
+    /// Fit the windows on monitors whose work area changed into the new one,
+    /// keeping their position and size relative to it. Windows that a layout
+    /// put in place get the layout applied again instead.
+    unsafe fn fit_work_areas(&mut self, before: &[MONITORINFO]) {
+        let laid_out = self.reapply_layouts();
+
+        for to in monitor_infos() {
+            let Some(from) = before.iter().find(|mi| mi.rcMonitor == to.rcMonitor) else {
+                continue;
+            };
+
+            if from.rcWork == to.rcWork {
+                continue;
+            }
+
+            for hwnd in window::app_windows() {
+                if IsIconic(hwnd).as_bool()
+                    || IsZoomed(hwnd).as_bool()
+                    || self.excluded(hwnd)
+                    || laid_out.contains(&(hwnd.0 as isize))
+                {
+                    continue;
+                }
+
+                if let (Some(mi), Some(rect)) = (monitor_info(hwnd), window::rect(hwnd))
+                    && mi.rcMonitor == to.rcMonitor
+                {
+                    place_window(
+                        hwnd,
+                        scale_rect(rect, from.rcWork, to.rcWork),
+                        self.margin(&to),
+                        &to,
+                    );
+                }
+            }
+
+            for zone in &mut self.zones {
+                if zone.mi.rcMonitor == to.rcMonitor {
+                    let (x, y, w, h) = zone.rect;
+                    let rect = RECT {
+                        left: x,
+                        top: y,
+                        right: x + w,
+                        bottom: y + h,
+                    };
+
+                    zone.rect = scale_rect(rect, from.rcWork, to.rcWork);
+                    zone.mi = to;
+                }
+            }
+        }
+    }
+
+    /// Roll a window up to its title bar, or back down to the height it had
+    unsafe fn toggle_shade(&mut self, hwnd: HWND) {
+        if let Some(height) = self.shaded.remove(&(hwnd.0 as isize)) {
+            set_height(hwnd, height);
+            return;
+        }
+
+        if IsZoomed(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        let Some(rect) = window::rect(hwnd) else {
+            return;
+        };
+
+        self.shaded.insert(hwnd.0 as isize, rect.bottom - rect.top);
+        set_height(hwnd, caption_height(hwnd, rect));
+    }
+
+    /// Pin a window to every desktop, or unpin it
+    unsafe fn toggle_pin(&self, hwnd: HWND) {
+        if let Err(err) = desktop::toggle_pinned(hwnd) {
+            report::problems(
+                "winmgr",
+                &[format!("Could not pin or unpin the window: {err}")],
+            );
+        }
+    }
+
+    unsafe fn toggle_scratchpad(&mut self, exe: &str, layout: Layout, options: &BindOptions) {
+        let key = exe.to_lowercase();
+
+        // Show the window again unless it was shown by other means meanwhile
+        if let Some(hwnd) = self.scratchpads.remove(&key)
+            && IsWindow(Some(hwnd)).as_bool()
+            && !IsWindowVisible(hwnd).as_bool()
+        {
+            let _ = ShowWindow(hwnd, SW_SHOW);
+            self.apply_layout(hwnd, layout, options);
+            focus_window(hwnd);
+            return;
+        }
+
+        let window = WindowMatch {
+            exe: Some(exe.to_owned()),
+            class: None,
+            title: None,
+        };
+
+        let Some(hwnd) = window::app_windows()
+            .into_iter()
+            .find(|&hwnd| window.matches(hwnd))
+        else {
+            launch(exe, &[], None);
+            return;
+        };
+
+        // Only put away once it has been brought to the front
+        if GetForegroundWindow() != hwnd {
+            focus_window(hwnd);
+            return;
+        }
+
+        let _ = ShowWindow(hwnd, SW_HIDE);
+        self.scratchpads.insert(key, hwnd);
+    }
+
+    unsafe fn dispatch(&mut self, action: &Action, options: &BindOptions) {
+        let hwnd: HWND = target_window(options.target.unwrap_or_default());
+
+        self.dispatch_to(hwnd, action, options);
+    }
+
+    /// Run an action on the given window rather than the binding's target
+    unsafe fn dispatch_to(&mut self, hwnd: HWND, action: &Action, options: &BindOptions) {
+        if action.targets_window() && hwnd.is_invalid() {
+            // No active window
+            return;
+        }
+
+        if action.targets_window() && self.excluded(hwnd) {
+            return;
+        }
+
+        let options = match action.targets_window() {
+            true => self.ruled_options(hwnd, options),
+            false => Cow::Borrowed(options),
+        };
+        let options = options.as_ref();
+
+        if action.moves_window() {
+            self.remember(hwnd);
+            self.leave_zone(hwnd);
+        }
+
+        if self.recent.len() == RECENT_ACTIONS {
+            self.recent.pop_front();
+        }
+
+        self.recent.push_back((Instant::now(), action.clone()));
+
+        let foreground = GetForegroundWindow();
+
+        match action {
+            Action::ApplyLayout(layout) => {
+                self.cycles.remove(&(hwnd.0 as isize));
+                self.apply_layout(hwnd, *layout, options);
+                self.note_layout(hwnd, *layout, options);
+            }
+            Action::Maximize => {
+                let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+            }
+            Action::RestoreIfMaximized => {
+                if IsZoomed(hwnd).as_bool() {
+                    let _ = ShowWindow(hwnd, SW_RESTORE);
+                }
+            }
+            Action::MaximizeToggle => self.maximize_toggle(hwnd),
+            Action::Minimize => {
+                let _ = ShowWindow(hwnd, SW_MINIMIZE);
+            }
+            Action::MinimizeOthers => {
+                // Without activating them on the way, so the window keeps focus
+                for other in window::same_monitor(hwnd) {
+                    if other != hwnd && !self.excluded(other) {
+                        let _ = ShowWindow(other, SW_SHOWMINNOACTIVE);
+                    }
+                }
+            }
+            Action::ToggleDesktop => self.toggle_desktop(),
+            Action::Close => {
+                let _ = PostMessageW(Some(hwnd), WM_CLOSE, WPARAM(0), LPARAM(0));
+            }
+            Action::ForceClose => self.force_close(hwnd),
+            Action::CenterKeepSize => {
+                if let Some(mi) = base_monitor_info(hwnd, self.monitor_from(options)) {
+                    center_keep_size(hwnd, &mi);
+                }
+            }
+            Action::ToggleAlwaysOnTop => toggle_always_on_top(hwnd),
+            Action::OpacityUp => set_opacity(hwnd, opacity(hwnd).saturating_add(OPACITY_STEP)),
+            Action::OpacityDown => set_opacity(hwnd, opacity(hwnd).saturating_sub(OPACITY_STEP)),
+            Action::OpacitySet(percent) => set_opacity(hwnd, *percent),
+            Action::ApplyRules => self.apply_rules(hwnd, options),
+            Action::Undo => self.undo(hwnd),
+            Action::RestoreOriginal => {
+                if let Some(&placement) = self.originals.get(&(hwnd.0 as isize)) {
+                    self.cycles.remove(&(hwnd.0 as isize));
+                    restore_placement(hwnd, placement);
+                }
+            }
+            Action::MoveBy(dx, dy) => {
+                let step = options.step.unwrap_or(self.cfg.move_step);
+                move_by(hwnd, dx * step, dy * step);
+            }
+            Action::ResizeBy(dw, dh) => {
+                let step = options.step.unwrap_or(self.cfg.resize_step);
+                resize_by(hwnd, dw * step, dh * step);
+            }
+            Action::SendToMonitor(target) => self.send_to_monitor(hwnd, target),
+            Action::MirrorToMonitor(target) => self.mirror_to_monitor(hwnd, target, options),
+            Action::FocusLeft => focus_neighbour(hwnd, Direction::Left),
+            Action::FocusRight => focus_neighbour(hwnd, Direction::Right),
+            Action::FocusUp => focus_neighbour(hwnd, Direction::Up),
+            Action::FocusDown => focus_neighbour(hwnd, Direction::Down),
+            Action::Switcher => {
+                let windows = window::app_windows()
+                    .into_iter()
+                    .filter(|&hwnd| !self.excluded(hwnd))
+                    .collect();
+
+                switcher::open(windows);
+            }
+            Action::FocusLast => {
+                if let Some(previous) = hooks::previous_focus()
+                    && IsWindow(Some(previous)).as_bool()
+                {
+                    focus_window(previous);
+                }
+            }
+            Action::SwapLeft => swap_with_neighbour(hwnd, Direction::Left),
+            Action::SwapRight => swap_with_neighbour(hwnd, Direction::Right),
+            Action::Cascade => self.cascade(hwnd),
+            Action::TileAll => self.tile_all(hwnd, options),
+            Action::Spiral => self.spiral(hwnd),
+            Action::TagWindow(slot) => {
+                self.tags.insert(*slot, hwnd);
+            }
+            Action::JumpToTag(slot) => self.jump_to_tag(*slot),
+            Action::ResizeMode => self.enter_resize_mode(),
+            Action::TogglePause => self.set_paused(!self.paused),
+            Action::ToggleKeybinds(name) => {
+                self.set_keybinds_enabled(name, self.disabled.contains(name));
+            }
+            Action::SwitchLayer(name) => self.switch_layer(name),
+            Action::SwitchProfile(name) => {
+                self.switch_profile(name);
+            }
+            Action::ReloadConfig => {
+                self.reload();
+            }
+            Action::Quit => self.shutdown(),
+            Action::RunCommand { program, args, cwd } => launch(program, args, cwd.as_deref()),
+            Action::FocusOrLaunch { exe, args } => focus_or_launch(exe, args),
+            Action::Scratchpad { exe, layout } => self.toggle_scratchpad(exe, *layout, options),
+            Action::SwitchDesktop(n) => {
+                if let Some(index) = n.checked_sub(1) {
+                    desktop::switch_to(index as usize);
+                }
+            }
+            Action::ToggleBorderless => self.toggle_borderless(hwnd),
+            Action::ToggleShade => self.toggle_shade(hwnd),
+            Action::TogglePinWindow => self.toggle_pin(hwnd),
+            Action::ToggleTaskbarAutoHide => self.toggle_taskbar_autohide(),
+            Action::SendToDesktop(n) => self.send_to_desktop(hwnd, *n, false),
+            Action::SendToDesktopAndFollow(n) => self.send_to_desktop(hwnd, *n, true),
+            Action::Macro(steps) => self.run_macro(steps.clone(), options),
+            Action::Arrange(layouts) => self.arrange(hwnd, layouts, options),
+            Action::PushColumn(layout) => self.push_column(hwnd, *layout, options),
+            Action::StackOnto(layout) => self.stack_onto(hwnd, *layout, options),
+            Action::Delay(_) => {
+                // Only meaningful as a macro step
+            }
+        }
+
+        if action.moves_window() && options.activate.unwrap_or(self.cfg.activate) {
+            focus_window(hwnd);
+        }
+
+        if options.warp_cursor.unwrap_or(self.cfg.warp_cursor) {
+            if action.moves_window() {
+                warp_cursor(hwnd);
+            } else if action.focuses_window() && GetForegroundWindow() != foreground {
+                warp_cursor(GetForegroundWindow());
+            }
+        }
+    }
+
+    /// Run macro steps up to the next delay, then schedule the rest on a timer
+    unsafe fn run_macro(&mut self, steps: Vec<Action>, options: &BindOptions) {
+        let mut steps = steps.into_iter();
+
+        while let Some(step) = steps.next() {
+            if let Action::Delay(ms) = step {
+                let timer = SetTimer(None, 0, ms, None);
+
+                self.macros.push(PendingMacro {
+                    timer,
+                    steps: steps.collect(),
+                    options: options.clone(),
+                });
+
+                return;
+            }
+
+            self.dispatch(&step, options);
+        }
+    }
+
+    /// Apply the next layout in a keybind's cycle. The cycle restarts when the
+    /// window was last placed by a different keybind.
+    unsafe fn cycle(&mut self, keybind: usize) {
+        let target = self.keybinds[keybind].options.target;
+        let hwnd: HWND = target_window(target.unwrap_or_default());
+
+        if hwnd.is_invalid() || self.excluded(hwnd) {
+            // No active window, or one to leave alone
+            return;
+        }
+
+        let layouts = self.keybinds[keybind].cycle_layouts();
+
+        let position = match self.cycles.get(&(hwnd.0 as isize)) {
+            Some(state) if state.keybind == keybind => (state.position + 1) % layouts.len(),
+            _ => 0,
+        };
+
+        let options = self.keybinds[keybind].options.clone();
+
+        self.remember(hwnd);
+        self.apply_layout(hwnd, layouts[position], &options);
+        self.note_layout(hwnd, layouts[position], &options);
+        self.cycles
+            .insert(hwnd.0 as isize, CycleState { keybind, position });
+    }
+
+    /// Switch the keybinds with the given name off or back on
+    unsafe fn set_keybinds_enabled(&mut self, name: &str, enabled: bool) {
+        let changed = match enabled {
+            true => self.disabled.remove(name),
+            false => self.disabled.insert(name.to_owned()),
+        };
+
+        if changed {
+            self.unregister();
+            self.register();
+        }
+    }
+
+    /// Unregister every binding except those that toggle pause, or restore them all
+    unsafe fn set_paused(&mut self, paused: bool) {
+        if self.paused == paused {
+            return;
+        }
+
+        if let Some(chord) = self.chord.take() {
+            self.exit_chord(&chord);
+        }
+
+        self.exit_resize_mode();
+        self.unregister();
+        self.paused = paused;
+        self.register();
+    }
+
+    /// Swap in a freshly read config. If it can't be read or names keys or
+    /// modifiers that don't exist, the current keybinds stay registered and
+    /// this returns false.
+    unsafe fn reload(&mut self) -> bool {
+        self.config_modified = modified(&self.config_path);
+
+        let cfg = match get_config(&self.config_path) {
+            Ok(cfg) => cfg,
+            Err(err) => {
+                report::problems(
+                    "winmgr config problems",
+                    &[format!(
+                        "Failed to reload config, keeping the current one: {err}"
+                    )],
+                );
+                return false;
+            }
+        };
+
+        let problems = validate::combo_problems(&cfg);
+
+        if !problems.is_empty() {
+            let reason = "Failed to reload config, keeping the current one:".to_owned();
+            report::problems("winmgr config problems", &[vec![reason], problems].concat());
+            return false;
+        }
+
+        // Stay on the active layer if the new config still has it
+        let layer = self
+            .layer
+            .take()
+            .filter(|layer| cfg.layers.contains_key(layer));
+
+        self.cfg = cfg;
+        self.set_layer(layer);
+        true
+    }
+
+    /// Reload from the config of another profile, staying on the current one
+    /// if it can't be read. Returns whether it switched.
+    unsafe fn switch_profile(&mut self, name: &str) -> bool {
+        let profile = (name != "default").then_some(name);
+
+        let path = match profile_config_path(profile) {
+            Ok(path) if path.exists() => path,
+            Ok(_) => {
+                report::problems(
+                    "winmgr config problems",
+                    &[format!("There is no profile named {name}")],
+                );
+                return false;
+            }
+            Err(err) => {
+                report::problems(
+                    "winmgr config problems",
+                    &[format!("Failed to find profile {name}: {err}")],
+                );
+                return false;
+            }
+        };
+
+        let previous = mem::replace(&mut self.config_path, path);
+
+        match self.reload() {
+            true => {
+                self.profile = profile.map(str::to_owned);
+                true
+            }
+            false => {
+                self.config_path = previous;
+                self.config_modified = modified(&self.config_path);
+                false
+            }
+        }
+    }
+
+    /// Switch to the named layer, or back to the base keybinds for `default`
+    unsafe fn switch_layer(&mut self, name: &str) {
+        if name == "default" {
+            self.set_layer(None);
+            return;
+        }
+
+        if !self.cfg.layers.contains_key(name) {
+            report::problems(
+                "winmgr config problems",
+                &[format!("There is no keybind layer named {name}")],
+            );
+            return;
+        }
+
+        self.set_layer(Some(name.to_owned()));
+    }
+
+    /// Register the base keybinds plus those of the given layer in place of
+    /// the current set
+    unsafe fn set_layer(&mut self, layer: Option<String>) {
+        if let Some(chord) = self.chord.take() {
+            self.exit_chord(&chord);
+        }
+
+        self.exit_resize_mode();
+        self.unregister();
+        self.cycles.clear();
+
+        // Layer keybinds come first so they take precedence over base keybinds
+        // with the same combo
+        self.keybinds = layer
+            .as_ref()
+            .map(|layer| self.cfg.layers[layer].as_slice())
+            .unwrap_or_default()
+            .iter()
+            .chain(&self.cfg.keybinds)
+            .cloned()
+            .collect();
+
+        self.layer = layer;
+        self.register();
+        self.report();
+    }
+
+    /// Registrations and low-level hooks can silently stop working across
+    /// sleep and session changes, so start over with all of them
+    unsafe fn reregister(&mut self) {
+        self.unregister();
+        self.register();
+
+        report::log(&self.problems);
+    }
+
+    /// Release every hotkey, atom, hook and timer, then end the message loop
+    unsafe fn shutdown(&mut self) {
+        if let Some(chord) = self.chord.take() {
+            self.exit_chord(&chord);
+        }
+
+        for pending in self.macros.drain(..) {
+            let _ = KillTimer(None, pending.timer);
+        }
+
+        for pending in self.desktop_moves.drain(..) {
+            let _ = KillTimer(None, pending.timer);
+            let _ = ShowWindow(pending.hwnd, SW_SHOW);
+        }
+
+        for pending in self.closing.drain(..) {
+            let _ = KillTimer(None, pending.timer);
+        }
+
+        if let Some(pending) = self.work_area_change.take() {
+            let _ = KillTimer(None, pending.timer);
+        }
+
+        for timer in [self.places_timer.take(), self.restore_timer.take()]
+            .into_iter()
+            .flatten()
+        {
+            let _ = KillTimer(None, timer);
+        }
+
+        self.exit_resize_mode();
+        self.unregister();
+        switcher::close();
+
+        // Hidden windows have no taskbar button to get them back by
+        for (_, hwnd) in self.scratchpads.drain() {
+            let _ = ShowWindow(hwnd, SW_SHOW);
+        }
+
+        for (hwnd, (style, placement)) in self.borderless.drain() {
+            let hwnd = HWND(hwnd as *mut _);
+
+            if IsWindow(Some(hwnd)).as_bool() {
+                set_style(hwnd, style);
+                restore_placement(hwnd, placement);
+            }
+        }
+
+        for (hwnd, height) in self.shaded.drain() {
+            let hwnd = HWND(hwnd as *mut _);
+
+            if IsWindow(Some(hwnd)).as_bool() {
+                set_height(hwnd, height);
+            }
+        }
+
+        PostQuitMessage(0);
+    }
+
+    /// Show any problems found by the last registration
+    fn report(&self) -> Option<JoinHandle<()>> {
+        report::problems("winmgr keybind problems", &self.problems)
+    }
+
+    unsafe fn unregister(&mut self) {
+        for id in self.map.keys() {
+            let _ = UnregisterHotKey(None, *id as i32);
+            GlobalDeleteAtom(*id as u16);
+        }
+
+        self.map.clear();
+
+        hooks::set_mouse_binds(Vec::new());
+        hooks::set_gestures(Vec::new(), self.cfg.double_tap_ms);
+    }
+
+    unsafe fn on_gesture(&mut self, index: usize) {
+        if let Some(gesture) = self.cfg.gestures.get(index) {
+            let action = gesture.action.clone();
+            let options = gesture.options.clone();
+            self.dispatch(&action, &options);
+        }
+    }
+
+    unsafe fn on_mousebind(&mut self, index: usize) {
+        if let Some(bind) = self.cfg.mousebinds.get(index) {
+            let action = bind.action.clone();
+            let options = bind.options.clone();
+            self.dispatch(&action, &options);
+        }
+    }
+
+    /// Restart the settle timer on every change in the config directory
+    unsafe fn on_config_changed(&mut self) {
+        if let Some(timer) = self.reload_timer.take() {
+            let _ = KillTimer(None, timer);
+        }
+
+        let timer = SetTimer(None, 0, CONFIG_SETTLE_MS, None);
+
+        if timer != 0 {
+            self.reload_timer = Some(timer);
+        }
+    }
+
+    unsafe fn on_timer(&mut self, timer_id: usize) {
+        if self.reload_timer == Some(timer_id) {
+            self.reload_timer = None;
+            let _ = KillTimer(None, timer_id);
+
+            // Other files in the directory changed, or the config is mid-replace
+            let modified = modified(&self.config_path);
+
+            if modified.is_some() && modified != self.config_modified {
+                self.reload();
+            }
+        }
+
+        if let Some(chord) = self.chord.take_if(|chord| chord.timer == timer_id) {
+            self.exit_chord(&chord);
+        }
+
+        if let Some(index) = self.macros.iter().position(|m| m.timer == timer_id) {
+            let pending = self.macros.remove(index);
+            let _ = KillTimer(None, pending.timer);
+            self.run_macro(pending.steps, &pending.options);
+        }
+
+        if let Some(index) = self.desktop_moves.iter().position(|m| m.timer == timer_id) {
+            let pending = self.desktop_moves.remove(index);
+            let _ = KillTimer(None, pending.timer);
+            let _ = ShowWindow(pending.hwnd, SW_SHOW);
+
+            match pending.return_to {
+                Some(index) => {
+                    desktop::switch_to(index);
+                }
+                None => focus_window(pending.hwnd),
+            }
+        }
+
+        if let Some(index) = self.closing.iter().position(|c| c.timer == timer_id) {
+            let pending = self.closing.remove(index);
+            let _ = KillTimer(None, pending.timer);
+
+            // Gone, or the handle now belongs to another process's window
+            if IsWindow(Some(pending.hwnd)).as_bool()
+                && window::process_id(pending.hwnd) == Some(pending.pid)
+            {
+                offer_terminate(pending.hwnd, pending.pid);
+            }
+        }
+
+        if self.places_timer == Some(timer_id) && self.cfg.restore_on_reconnect {
+            self.note_places();
+        }
+
+        if self
+            .restore_timer
+            .take_if(|&mut timer| timer == timer_id)
+            .is_some()
+        {
+            let _ = KillTimer(None, timer_id);
+            self.restore_places();
+            self.note_places();
+        }
+
+        if let Some(pending) = self.work_area_change.take_if(|p| p.timer == timer_id) {
+            let _ = KillTimer(None, pending.timer);
+            self.fit_work_areas(&pending.before);
+        }
+    }
+
+    /// Register the follow-up keys of a chord and start its timeout
+    unsafe fn enter_chord(&mut self, keybind: usize) {
+        for (offset, bind) in self.keybinds[keybind].chord.iter().enumerate() {
+            // Reported when the keybind was registered
+            let Ok((mods, key)) = parse_combo(&bind.modifiers, &bind.key) else {
+                continue;
+            };
+
+            let id = CHORD_HOTKEY_BASE + offset as i32;
+
+            if let Err(err) = RegisterHotKey(None, id, mods | MOD_NOREPEAT, key.0.into()) {
+                eprintln!("Failed to register chord key {offset} of keybind {keybind}: {err}");
+            }
+        }
+
+        if let Err(err) =
+            RegisterHotKey(None, CHORD_CANCEL_HOTKEY, MOD_NOREPEAT, VK_ESCAPE.0.into())
+        {
+            eprintln!("Failed to register chord cancel key: {err}");
+        }
+
+        let timer = SetTimer(None, 0, self.cfg.chord_timeout_ms, None);
+
+        self.chord = Some(ActiveChord { keybind, timer });
+    }
+
+    unsafe fn exit_chord(&self, chord: &ActiveChord) {
+        for offset in 0..self.keybinds[chord.keybind].chord.len() {
+            let _ = UnregisterHotKey(None, CHORD_HOTKEY_BASE + offset as i32);
+        }
+
+        let _ = UnregisterHotKey(None, CHORD_CANCEL_HOTKEY);
+        let _ = KillTimer(None, chord.timer);
+    }
+
+    /// Register the arrow keys for resizing and the keys that exit the mode.
+    /// Resize keys repeat while held.
+    unsafe fn enter_resize_mode(&mut self) {
+        if self.resizing {
+            return;
+        }
+
+        let keys = RESIZE_KEYS
+            .iter()
+            .map(|(key, _, _)| (*key, HOT_KEY_MODIFIERS(0)))
+            .chain(RESIZE_EXIT_KEYS.iter().map(|key| (*key, MOD_NOREPEAT)));
+
+        for (offset, (key, mods)) in keys.enumerate() {
+            let id = RESIZE_HOTKEY_BASE + offset as i32;
+
+            if let Err(err) = RegisterHotKey(None, id, mods, key.0.into()) {
+                eprintln!("Failed to register resize mode key {offset}: {err}");
+            }
+        }
+
+        self.resizing = true;
+    }
+
+    unsafe fn exit_resize_mode(&mut self) {
+        if !self.resizing {
+            return;
+        }
+
+        for offset in 0..RESIZE_KEYS.len() + RESIZE_EXIT_KEYS.len() {
+            let _ = UnregisterHotKey(None, RESIZE_HOTKEY_BASE + offset as i32);
+        }
+
+        self.resizing = false;
+    }
+
+    unsafe fn on_resize_key(&mut self, offset: usize) {
+        let Some(&(_, dw, dh)) = RESIZE_KEYS.get(offset) else {
+            self.exit_resize_mode();
+            return;
+        };
+
+        let hwnd: HWND = GetForegroundWindow();
+
+        if hwnd.is_invalid() || self.excluded(hwnd) {
+            // No active window, or one to leave alone
+            return;
+        }
+
+        let step = self.cfg.resize_step;
+
+        self.remember(hwnd);
+        resize_by(hwnd, dw * step, dh * step);
+    }
+
+    unsafe fn apply_layout(&self, hwnd: HWND, layout: Layout, options: &BindOptions) {
+        let Some(mi) = layout_monitor_info(
+            hwnd,
+            layout,
+            options,
+            self.monitor_from(options),
+            &self.display_names,
+        ) else {
+            return;
+        };
+
+        let mut zone = self.layout_rect(layout, options, &mi);
+
+        if options.keep_aspect
+            && let Some(rect) = window::rect(hwnd)
+        {
+            zone = fit_aspect(zone, &rect);
+        }
+
+        place_window(hwnd, zone, self.margin(&mi), &mi);
+    }
+
+    unsafe fn send_to_monitor(&self, hwnd: HWND, target: &MonitorTarget) {
+        let (Some(from), Some(to)) = (
+            monitor_info(hwnd),
+            target_monitor_info(hwnd, target, MonitorFrom::Window, &self.display_names),
+        ) else {
+            return;
+        };
+
+        if from.rcMonitor == to.rcMonitor {
+            return;
+        }
+
+        let maximized = IsZoomed(hwnd).as_bool();
+
+        if maximized {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        let Some(rect) = window::rect(hwnd) else {
+            return;
+        };
+
+        let zone = scale_rect(rect, from.rcWork, to.rcWork);
+
+        place_window(hwnd, zone, self.margin(&to), &to);
+
+        if maximized {
+            let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+        }
+    }
+
+    unsafe fn mirror_to_monitor(
+        &mut self,
+        hwnd: HWND,
+        target: &MonitorTarget,
+        options: &BindOptions,
+    ) {
+        let (Some(from), Some(to), Some(rect)) = (
+            monitor_info(hwnd),
+            target_monitor_info(hwnd, target, MonitorFrom::Window, &self.display_names),
+            window::rect(hwnd),
+        ) else {
+            return;
+        };
+
+        if from.rcMonitor == to.rcMonitor {
+            return;
+        }
+
+        let Some((layout, options)) = self.filled_layout(hwnd, rect, &from, options) else {
+            self.send_to_monitor(hwnd, target);
+            return;
+        };
+
+        place_window(
+            hwnd,
+            self.layout_rect(layout, &options, &to),
+            self.margin(&to),
+            &to,
+        );
+        self.note_layout(hwnd, layout, &options);
+    }
+
+    /// The layout a window fills on its monitor: the one last applied to it
+    /// if it's still there, or else the predefined layout it lines up with
+    unsafe fn filled_layout(
+        &self,
+        hwnd: HWND,
+        rect: RECT,
+        mi: &MONITORINFO,
+        options: &BindOptions,
+    ) -> Option<(Layout, BindOptions)> {
+        if IsZoomed(hwnd).as_bool() {
+            return None;
+        }
+
+        // Custom layouts are tied to their own coordinates
+        if let Some((layout, options, applied)) = self.layouts.get(&(hwnd.0 as isize))
+            && *applied == rect
+            && !matches!(layout, Layout::Custom(_))
+        {
+            return Some((*layout, options.clone()));
+        }
+
+        DefaultLayout::ALL
+            .into_iter()
+            .map(Layout::Default)
+            .map(|layout| {
+                let (x, y, w, h) = self.layout_rect(layout, options, mi);
+                let off = [
+                    rect.left - x,
+                    rect.top - y,
+                    rect.right - (x + w),
+                    rect.bottom - (y + h),
+                ]
+                .map(i32::abs);
+
+                (off.into_iter().max().unwrap_or_default(), layout)
+            })
+            .filter(|&(off, _)| off <= SNAP_TOLERANCE)
+            .min_by_key(|&(off, _)| off)
+            .map(|(_, layout)| (layout, options.clone()))
+    }
+
+    unsafe fn push_column(&mut self, hwnd: HWND, layout: Layout, options: &BindOptions) {
+        let Some(mi) = layout_monitor_info(
+            hwnd,
+            layout,
+            options,
+            self.monitor_from(options),
+            &self.display_names,
+        ) else {
+            return;
+        };
+
+        let rect = self.layout_rect(layout, options, &mi);
+
+        if IsZoomed(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        self.cycles.remove(&(hwnd.0 as isize));
+
+        let index = match self.zones.iter().position(|zone| zone.rect == rect) {
+            Some(index) => index,
+            None => {
+                self.zones.push(Zone {
+                    rect,
+                    mi,
+                    windows: Vec::new(),
+                });
+                self.zones.len() - 1
+            }
+        };
+
+        self.zones[index].windows.push(hwnd);
+        self.tile_zone(index);
+    }
+
+    /// Lay a window over an occupant of a column, or apply the layout when no
+    /// window has been pushed into it
+    unsafe fn stack_onto(&mut self, hwnd: HWND, layout: Layout, options: &BindOptions) {
+        let Some(mi) = layout_monitor_info(
+            hwnd,
+            layout,
+            options,
+            self.monitor_from(options),
+            &self.display_names,
+        ) else {
+            return;
+        };
+
+        let rect = self.layout_rect(layout, options, &mi);
+
+        let occupant = self
+            .zones
+            .iter()
+            .find(|zone| zone.rect == rect)
+            .and_then(|zone| {
+                zone.windows
+                    .iter()
+                    .copied()
+                    .find(|&other| other != hwnd && IsWindow(Some(other)).as_bool())
+            })
+            .and_then(|other| window::rect(other));
+
+        if IsZoomed(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+
+        self.cycles.remove(&(hwnd.0 as isize));
+
+        match occupant {
+            Some(other) => {
+                let zone = (
+                    other.left,
+                    other.top,
+                    other.right - other.left,
+                    other.bottom - other.top,
+                );
+
+                place_window(hwnd, zone, self.margin(&mi), &mi);
+            }
+            None => place_window(hwnd, rect, self.margin(&mi), &mi),
+        }
+
+        focus_window(hwnd);
+    }
+
+    /// Take a window out of its column, closing the space it leaves
+    unsafe fn leave_zone(&mut self, hwnd: HWND) {
+        let Some(index) = self
+            .zones
+            .iter()
+            .position(|zone| zone.windows.contains(&hwnd))
+        else {
+            return;
+        };
+
+        self.zones[index].windows.retain(|&other| other != hwnd);
+        self.tile_zone(index);
+    }
+
+    /// Split a column evenly between its occupants, forgetting closed and
+    /// minimized ones
+    unsafe fn tile_zone(&mut self, index: usize) {
+        let margin = self.margin(&self.zones[index].mi);
+        let gap = self.gap(&self.zones[index].mi);
+        let zone = &mut self.zones[index];
+
+        zone.windows
+            .retain(|&hwnd| IsWindow(Some(hwnd)).as_bool() && !IsIconic(hwnd).as_bool());
+
+        if zone.windows.is_empty() {
+            self.zones.remove(index);
+            return;
+        }
+
+        let (x, y, w, h) = zone.rect;
+        let count = zone.windows.len() as i32;
+        let half_gap = gap as i32 / 2;
+
+        for (i, &hwnd) in (0..).zip(&zone.windows) {
+            let mut top = y + h * i / count;
+            let mut bottom = y + h * (i + 1) / count;
+
+            if i > 0 {
+                top += half_gap;
+            }
+
+            if i < count - 1 {
+                bottom -= half_gap;
+            }
+
+            place_window(hwnd, (x, top, w, bottom - top), margin, &zone.mi);
+        }
+    }
+
+    /// Apply the layouts in turn to `hwnd` and the windows below it in z-order,
+    /// which is most recently used first. Minimized windows are skipped.
+    unsafe fn arrange(&mut self, hwnd: HWND, layouts: &[Layout], options: &BindOptions) {
+        let Some(mi) = monitor_info(hwnd) else {
+            return;
+        };
+
+        let others = window::app_windows()
+            .into_iter()
+            .filter(|&other| other != hwnd && !IsIconic(other).as_bool() && !self.floating(other))
+            .collect::<Vec<_>>();
+
+        for (window, layout) in iter::once(hwnd).chain(others).zip(layouts) {
+            let zone = self.layout_rect(*layout, options, &mi);
+
+            self.prepare_move(window);
+            place_window(window, zone, self.margin(&mi), &mi);
+        }
+    }
+
+    unsafe fn cascade(&mut self, hwnd: HWND) {
+        let Some(mi) = monitor_info(hwnd) else {
+            return;
+        };
+
+        let m = self.margin(&mi) as i32;
+        let inner_width = (mi.rcWork.right - mi.rcWork.left - 2 * m).max(0);
+        let inner_height = (mi.rcWork.bottom - mi.rcWork.top - 2 * m).max(0);
+
+        let w = inner_width * 3 / 4;
+        let h = inner_height * 3 / 4;
+
+        // Start over from the corner once the offsets run out of room
+        let fits = ((inner_width - w).min(inner_height - h) / CASCADE_STEP).max(0) + 1;
+
+        let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED;
+
+        // Bottom of the z-order first, so the front window ends up furthest in
+        for (i, window) in self.tiled_windows(hwnd).into_iter().rev().enumerate() {
+            let offset = (i as i32 % fits) * CASCADE_STEP;
+
+            self.prepare_move(window);
+
+            let _ = SetWindowPos(
+                window,
+                None,
+                mi.rcWork.left + m + offset,
+                mi.rcWork.top + m + offset,
+                w,
+                h,
+                flags,
+            );
+        }
+    }
+
+    /// Grid as square as possible, filled in z-order from the top left
+    unsafe fn tile_all(&mut self, hwnd: HWND, options: &BindOptions) {
+        let Some(mi) = monitor_info(hwnd) else {
+            return;
+        };
+
+        let windows = self.tiled_windows(hwnd);
+        let count = windows.len() as u32;
+
+        if count == 0 {
+            return;
+        }
+
+        let cols = (1..=count).find(|cols| cols * cols >= count).unwrap_or(1);
+        let rows = count.div_ceil(cols);
+
+        for (i, window) in (0..).zip(windows) {
+            let cell = GridLayout {
+                cols,
+                rows,
+                col: i % cols,
+                row: i / cols,
+                col_span: 1,
+                row_span: 1,
+            };
+
+            let zone = self.layout_rect(Layout::Grid(cell), options, &mi);
+
+            self.prepare_move(window);
+            place_window(window, zone, self.margin(&mi), &mi);
+        }
+    }
+
+    /// Record where a window is, for `Undo` and `RestoreOriginal`
+    unsafe fn remember(&mut self, hwnd: HWND) {
+        let Some(rect) = window::rect(hwnd) else {
+            return;
+        };
+
+        let placement = Placement {
+            rect,
+            maximized: IsZoomed(hwnd).as_bool(),
+        };
+
+        self.originals.entry(hwnd.0 as isize).or_insert(placement);
+
+        let history = self.history.entry(hwnd.0 as isize).or_default();
+
+        if history.last() != Some(&placement) {
+            if history.len() == UNDO_DEPTH {
+                history.remove(0);
+            }
+
+            history.push(placement);
+        }
+    }
+
+    unsafe fn undo(&mut self, hwnd: HWND) {
+        let key = hwnd.0 as isize;
+
+        let Some(placement) = self.history.get_mut(&key).and_then(Vec::pop) else {
+            return;
+        };
+
+        self.cycles.remove(&key);
+
+        restore_placement(hwnd, placement);
+    }
+
+    /// Each window takes half of the space the previous ones left, turning
+    /// left, top, right, bottom in turn. The last window takes all that is left.
+    unsafe fn spiral(&mut self, hwnd: HWND) {
+        let Some(mi) = monitor_info(hwnd) else {
+            return;
+        };
+
+        let mut windows = self.tiled_windows(hwnd);
+        let count = windows.len();
+
+        // The target window isn't always top of the z-order, e.g. with `target: cursor`
+        if let Some(position) = windows.iter().position(|&other| other == hwnd) {
+            windows[..=position].rotate_right(1);
+        }
+
+        let m = self.margin(&mi) as i32;
+        let mut x = mi.rcWork.left + m;
+        let mut y = mi.rcWork.top + m;
+        let mut w = (mi.rcWork.right - mi.rcWork.left - 2 * m).max(0);
+        let mut h = (mi.rcWork.bottom - mi.rcWork.top - 2 * m).max(0);
+
+        for (i, window) in windows.into_iter().enumerate() {
+            let rect = if i == count - 1 {
+                (x, y, w, h)
+            } else {
+                match i % 4 {
+                    0 => {
+                        let half = w / 2;
+                        x += half;
+                        w -= half;
+                        (x - half, y, half, h)
+                    }
+                    1 => {
+                        let half = h / 2;
+                        y += half;
+                        h -= half;
+                        (x, y - half, w, half)
+                    }
+                    2 => {
+                        let half = w / 2;
+                        w -= half;
+                        (x + w, y, half, h)
+                    }
+                    _ => {
+                        let half = h / 2;
+                        h -= half;
+                        (x, y + h, w, half)
+                    }
+                }
+            };
+
+            let zone = apply_gap(rect, self.margin(&mi), self.gap(&mi), &mi);
+
+            self.prepare_move(window);
+            place_window(window, zone, self.margin(&mi), &mi);
+        }
+    }
+
+    /// Take a window out of any cycle or column and out of the maximized state
+    /// before moving it somewhere new
+    unsafe fn prepare_move(&mut self, hwnd: HWND) {
+        self.remember(hwnd);
+        self.leave_zone(hwnd);
+        self.cycles.remove(&(hwnd.0 as isize));
+
+        if IsZoomed(hwnd).as_bool() {
+            let _ = ShowWindow(hwnd, SW_RESTORE);
+        }
+    }
+
+    /// Which monitor layouts go on when the keybind doesn't name one
+    fn monitor_from(&self, options: &BindOptions) -> MonitorFrom {
+        options.monitor_from.unwrap_or(self.cfg.monitor_from)
+    }
+
+    /// Margin on the given monitor
+    unsafe fn margin(&self, mi: &MONITORINFO) -> u8 {
+        self.monitor_settings(mi)
+            .and_then(|settings| settings.margin)
+            .unwrap_or(self.cfg.margin)
+    }
+
+    /// Gap on the given monitor
+    unsafe fn gap(&self, mi: &MONITORINFO) -> u8 {
+        self.monitor_settings(mi)
+            .and_then(|settings| settings.gap)
+            .unwrap_or(self.cfg.gap)
+    }
+
+    unsafe fn monitor_settings(&self, mi: &MONITORINFO) -> Option<&MonitorSettings> {
+        if self.cfg.monitors.is_empty() {
+            return None;
+        }
+
+        self.cfg
+            .monitors
+            .iter()
+            .find(|(key, _)| monitor_named(mi, key, &self.display_names))
+            .map(|(_, settings)| settings)
+    }
+
+    /// Calculate (x, y, w, h) of a layout on the given monitor
+    unsafe fn layout_rect(
+        &self,
+        layout: Layout,
+        options: &BindOptions,
+        mi: &MONITORINFO,
+    ) -> (i32, i32, i32, i32) {
+        match layout {
+            Layout::Custom(layout) => layout.calc(mi),
+            Layout::Grid(layout) => apply_gap(
+                layout.calc(self.margin(mi), mi),
+                self.margin(mi),
+                self.gap(mi),
+                mi,
+            ),
+            Layout::Default(layout) => apply_gap(
+                layout.calc(
+                    self.margin(mi),
+                    options
+                        .ratio
+                        .or_else(|| layout.split_share(self.cfg.split_ratio)),
+                    mi,
+                ),
+                self.margin(mi),
+                self.gap(mi),
+                mi,
+            ),
+        }
+    }
+}
+
+/// Monitor a layout is placed on: the one it or the binding names, or else the
+/// window's own
+unsafe fn layout_monitor_info(
+    hwnd: HWND,
+    layout: Layout,
+    options: &BindOptions,
+    from: MonitorFrom,
+    names: &HashMap<String, String>,
+) -> Option<MONITORINFO> {
+    let target = match layout {
+        Layout::Custom(layout) => layout
+            .monitor
+            .map(MonitorTarget::Number)
+            .or(options.monitor.clone()),
+        _ => options.monitor.clone(),
+    };
+
+    match target {
+        Some(target) => target_monitor_info(hwnd, &target, from, names),
+        None => base_monitor_info(hwnd, from),
+    }
+}
+
+/// Monitor picked by `target`, with `next` and `prev` relative to the one
+/// `from` picks
+unsafe fn target_monitor_info(
+    hwnd: HWND,
+    target: &MonitorTarget,
+    from: MonitorFrom,
+    names: &HashMap<String, String>,
+) -> Option<MONITORINFO> {
+    let infos = monitor_infos();
+    let count = infos.len().max(1);
+
+    let current = base_monitor_info(hwnd, from)
+        .and_then(|mi| {
+            infos
+                .iter()
+                .position(|other| other.rcMonitor == mi.rcMonitor)
+        })
+        .unwrap_or(0);
+
+    let index = match target {
+        MonitorTarget::Number(n) => n.checked_sub(1)? as usize,
+        MonitorTarget::Adjacent(Adjacent::Next) => (current + 1) % count,
+        MonitorTarget::Adjacent(Adjacent::Prev) => (current + count - 1) % count,
+        MonitorTarget::Name(name) => infos.iter().position(|mi| monitor_named(mi, name, names))?,
+    };
+
+    infos.get(index).copied()
+}
+
+unsafe fn base_monitor_info(hwnd: HWND, from: MonitorFrom) -> Option<MONITORINFO> {
+    match from {
+        MonitorFrom::Window => monitor_info(hwnd),
+        MonitorFrom::Cursor => {
+            let mut point = POINT::default();
+            GetCursorPos(&mut point).ok()?;
+
+            handle_monitor_info(MonitorFromPoint(point, MONITOR_DEFAULTTONEAREST))
+        }
+        MonitorFrom::Primary => {
+            handle_monitor_info(MonitorFromPoint(POINT::default(), MONITOR_DEFAULTTOPRIMARY))
+        }
+    }
+}
+
+/// `rect` moved from one area to another, scaled along with it, as
+/// `(x, y, width, height)`
+fn scale_rect(rect: RECT, from: RECT, to: RECT) -> (i32, i32, i32, i32) {
+    let scale_x = (to.right - to.left) as f32 / (from.right - from.left).max(1) as f32;
+    let scale_y = (to.bottom - to.top) as f32 / (from.bottom - from.top).max(1) as f32;
+
+    (
+        to.left + ((rect.left - from.left) as f32 * scale_x).round() as i32,
+        to.top + ((rect.top - from.top) as f32 * scale_y).round() as i32,
+        ((rect.right - rect.left) as f32 * scale_x).round() as i32,
+        ((rect.bottom - rect.top) as f32 * scale_y).round() as i32,
+    )
+}
+
+/// Identifies a set of monitors by where each one is and how big it is
+fn topology(monitors: &[MONITORINFO]) -> u64 {
+    let mut hasher = DefaultHasher::new();
+
+    for mi in monitors {
+        let r = mi.rcMonitor;
+        (r.left, r.top, r.right, r.bottom).hash(&mut hasher);
+    }
+
+    hasher.finish()
+}
+
+/// Height of the window down to the bottom of its title bar
+unsafe fn caption_height(hwnd: HWND, rect: RECT) -> i32 {
+    let mut client = POINT::default();
+    let _ = ClientToScreen(hwnd, &mut client);
+
+    // Windows drawing their own title bar start their client area at the top
+    match client.y - rect.top {
+        height if height > 0 => height,
+        _ => GetSystemMetrics(SM_CYCAPTION) + GetSystemMetrics(SM_CYFRAME),
+    }
+}
+
+/// Resize a window to `height`, keeping its top edge and width
+unsafe fn set_height(hwnd: HWND, height: i32) {
+    let Some(rect) = window::rect(hwnd) else {
+        return;
+    };
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOMOVE | SWP_NOZORDER | SWP_NOACTIVATE;
+
+    let _ = SetWindowPos(hwnd, None, 0, 0, rect.right - rect.left, height, flags);
+}
+
+unsafe fn restore_placement(hwnd: HWND, placement: Placement) {
+    if placement.maximized {
+        let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+        return;
+    }
+
+    if IsZoomed(hwnd).as_bool() {
+        let _ = ShowWindow(hwnd, SW_RESTORE);
+    }
+
+    let rect = placement.rect;
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE;
+
+    let _ = SetWindowPos(
+        hwnd,
+        None,
+        rect.left,
+        rect.top,
+        rect.right - rect.left,
+        rect.bottom - rect.top,
+        flags,
+    );
+}
+
+/// Largest rect with the aspect ratio of `rect` that fits in the zone, centered
+/// in it
+fn fit_aspect((x, y, w, h): (i32, i32, i32, i32), rect: &RECT) -> (i32, i32, i32, i32) {
+    let rect_w = (rect.right - rect.left) as i64;
+    let rect_h = (rect.bottom - rect.top) as i64;
+
+    if rect_w <= 0 || rect_h <= 0 {
+        return (x, y, w, h);
+    }
+
+    // Compare w / h against rect_w / rect_h without dividing
+    let (fit_w, fit_h) = if w as i64 * rect_h > h as i64 * rect_w {
+        ((h as i64 * rect_w / rect_h) as i32, h)
+    } else {
+        (w, (w as i64 * rect_h / rect_w) as i32)
+    };
+
+    (x + (w - fit_w) / 2, y + (h - fit_h) / 2, fit_w, fit_h)
+}
+
+/// Move and size a window to fill a zone, given as (x, y, w, h). Some windows
+/// enforce a minimum or maximum size or snap to a character grid, and end up
+/// a different size. Those are lined up with the edges of the zone that lie on
+/// the margin, or centered in the zone otherwise.
+unsafe fn place_window(
+    hwnd: HWND,
+    (x, y, w, h): (i32, i32, i32, i32),
+    margin: u8,
+    mi: &MONITORINFO,
+) {
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED;
+    let crossing_dpi = GetDpiForWindow(hwnd) != monitor_dpi(mi);
+
+    if SetWindowPos(hwnd, None, x, y, w, h, flags).is_err() {
+        return;
+    }
+
+    // Reaching a monitor with another DPI, the window gets `WM_DPICHANGED`
+    // and resizes itself to the size it suggests, in place of the one just
+    // set. Once it has caught up, the rect is set again.
+    if crossing_dpi {
+        SendMessageTimeoutW(
+            hwnd,
+            WM_NULL,
+            WPARAM(0),
+            LPARAM(0),
+            SMTO_ABORTIFHUNG,
+            DPI_CHANGE_TIMEOUT_MS,
+            None,
+        );
+
+        let _ = SetWindowPos(hwnd, None, x, y, w, h, flags);
+    }
+
+    let Some(actual) = window::rect(hwnd) else {
+        return;
+    };
+
+    let actual_w = actual.right - actual.left;
+    let actual_h = actual.bottom - actual.top;
+
+    if (actual_w, actual_h) == (w, h) {
+        return;
+    }
+
+    let m = margin as i32;
+
+    // Start of the window along one axis, given the zone's start and size
+    let align = |start: i32, size: i32, actual: i32, low: i32, high: i32| {
+        if start <= low + m + 1 {
+            start
+        } else if start + size >= high - m - 1 {
+            start + size - actual
+        } else {
+            start + (size - actual) / 2
+        }
+    };
+
+    let new_x = align(x, w, actual_w, mi.rcWork.left, mi.rcWork.right);
+    let new_y = align(y, h, actual_h, mi.rcWork.top, mi.rcWork.bottom);
+
+    if (new_x, new_y) != (actual.left, actual.top) {
+        let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_NOSIZE;
+
+        let _ = SetWindowPos(hwnd, None, new_x, new_y, 0, 0, flags);
+    }
+}
+
+/// Pull in the edges of a layout that don't lie on the margin, by half the gap
+/// each, so that two adjacent layouts end up a whole gap apart
+fn apply_gap(
+    (x, y, w, h): (i32, i32, i32, i32),
+    margin: u8,
+    gap: u8,
+    mi: &MONITORINFO,
+) -> (i32, i32, i32, i32) {
+    let m = margin as i32;
+    let half = gap as i32 / 2;
+
+    if half == 0 {
+        return (x, y, w, h);
+    }
+
+    // Layouts divide with integer maths, so allow for a pixel of rounding
+    let inset = |edge: i32, bound: i32| if (edge - bound).abs() <= 1 { 0 } else { half };
+
+    let left = x + inset(x, mi.rcWork.left + m);
+    let top = y + inset(y, mi.rcWork.top + m);
+    let right = x + w - inset(x + w, mi.rcWork.right - m);
+    let bottom = y + h - inset(y + h, mi.rcWork.bottom - m);
+
+    (left, top, (right - left).max(0), (bottom - top).max(0))
+}
+
+/// Bounding rect of all monitors
+unsafe fn virtual_screen() -> RECT {
+    let left = GetSystemMetrics(SM_XVIRTUALSCREEN);
+    let top = GetSystemMetrics(SM_YVIRTUALSCREEN);
+
+    RECT {
+        left,
+        top,
+        right: left + GetSystemMetrics(SM_CXVIRTUALSCREEN),
+        bottom: top + GetSystemMetrics(SM_CYVIRTUALSCREEN),
+    }
+}
+
+unsafe fn target_window(target: Target) -> HWND {
+    match target {
+        Target::Foreground => GetForegroundWindow(),
+        Target::Cursor => {
+            let mut point = POINT::default();
+
+            if GetCursorPos(&mut point).is_err() {
+                return HWND::default();
+            }
+
+            GetAncestor(WindowFromPoint(point), GA_ROOT)
+        }
+    }
+}
+
+unsafe fn monitor_info(hwnd: HWND) -> Option<MONITORINFO> {
+    handle_monitor_info(MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST))
+}
+
+unsafe fn handle_monitor_info(monitor: HMONITOR) -> Option<MONITORINFO> {
+    let mut mi = MONITORINFO {
+        cbSize: size_of::<MONITORINFO>() as u32,
+        ..Default::default()
+    };
+
+    if !GetMonitorInfoW(monitor, &mut mi).as_bool() {
+        // Could not query monitor info
+        eprintln!("Could not query monitor info");
+        return None;
+    }
+
+    Some(mi)
+}
+
+/// Info of the n-th monitor counting from 1, with monitors numbered left to
+/// right and then top to bottom
+unsafe fn nth_monitor_info(n: u32) -> Option<MONITORINFO> {
+    monitor_infos().into_iter().nth(n.checked_sub(1)? as usize)
+}
+
+/// A rect as `[left, top, right, bottom]`, for printing and saving
+fn corners(rect: RECT) -> [i32; 4] {
+    [rect.left, rect.top, rect.right, rect.bottom]
+}
+
+/// Number of a monitor as counted by `nth_monitor_info`
+unsafe fn monitor_number(mi: &MONITORINFO) -> Option<u32> {
+    monitor_infos()
+        .iter()
+        .position(|other| other.rcMonitor == mi.rcMonitor)
+        .map(|index| index as u32 + 1)
+}
+
+/// Whether `name` names the monitor, as a number counting from 1, a device
+/// name or one of the friendly `names` from `display::friendly_names`. The
+/// comparisons are case-insensitive.
+unsafe fn monitor_named(mi: &MONITORINFO, name: &str, names: &HashMap<String, String>) -> bool {
+    if let Ok(number) = name.parse() {
+        return monitor_number(mi) == Some(number);
+    }
+
+    let name = name.trim_start_matches(r"\\.\");
+
+    let Some(device) = monitor_device_name(mi) else {
+        return false;
+    };
+
+    device.eq_ignore_ascii_case(name)
+        || names
+            .get(&device)
+            .is_some_and(|friendly| friendly.eq_ignore_ascii_case(name))
+}
+
+/// Device name of a monitor without the `\\.\` prefix, e.g. `DISPLAY1`
+unsafe fn monitor_device_name(mi: &MONITORINFO) -> Option<String> {
+    let monitor = MonitorFromRect(&mi.rcMonitor, MONITOR_DEFAULTTONEAREST);
+
+    let mut info = MONITORINFOEXW::default();
+    info.monitorInfo.cbSize = size_of::<MONITORINFOEXW>() as u32;
+
+    if !GetMonitorInfoW(monitor, (&raw mut info).cast()).as_bool() {
+        return None;
+    }
+
+    let len = info
+        .szDevice
+        .iter()
+        .position(|&c| c == 0)
+        .unwrap_or(info.szDevice.len());
+    let name = String::from_utf16_lossy(&info.szDevice[..len]);
+
+    Some(name.trim_start_matches(r"\\.\").to_owned())
+}
+
+/// Effective DPI of a monitor, 96 at 100% scaling
+unsafe fn monitor_dpi(mi: &MONITORINFO) -> u32 {
+    let monitor = MonitorFromRect(&mi.rcMonitor, MONITOR_DEFAULTTONEAREST);
+
+    let mut dpi = 0;
+    let mut dpi_y = 0;
+    let _ = GetDpiForMonitor(monitor, MDT_EFFECTIVE_DPI, &mut dpi, &mut dpi_y);
+
+    dpi
+}
+
+/// Every monitor, left to right and then top to bottom
+unsafe fn monitor_infos() -> Vec<MONITORINFO> {
+    let mut monitors: Vec<HMONITOR> = Vec::new();
+
+    let _ = EnumDisplayMonitors(
+        None,
+        None,
+        Some(collect_monitor),
+        LPARAM(&raw mut monitors as isize),
+    );
+
+    let mut infos: Vec<MONITORINFO> = monitors
+        .into_iter()
+        .filter_map(|monitor| handle_monitor_info(monitor))
+        .collect();
+
+    infos.sort_by_key(|mi| (mi.rcMonitor.left, mi.rcMonitor.top));
+
+    infos
+}
+
+unsafe extern "system" fn collect_monitor(
+    monitor: HMONITOR,
+    _hdc: HDC,
+    _rect: *mut RECT,
+    lparam: LPARAM,
+) -> BOOL {
+    let monitors = &mut *(lparam.0 as *mut Vec<HMONITOR>);
+
+    monitors.push(monitor);
+
+    true.into()
+}
+
+/// Center the window on the work area of the given monitor without resizing it
+unsafe fn center_keep_size(hwnd: HWND, mi: &MONITORINFO) {
+    let mut rect = RECT::default();
+
+    if GetWindowRect(hwnd, &mut rect).is_err() {
+        return;
+    }
+
+    let w = rect.right - rect.left;
+    let h = rect.bottom - rect.top;
+    let x = mi.rcWork.left + (mi.rcWork.right - mi.rcWork.left - w) / 2;
+    let y = mi.rcWork.top + (mi.rcWork.bottom - mi.rcWork.top - h) / 2;
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_NOSIZE;
+
+    let _ = SetWindowPos(hwnd, None, x, y, w, h, flags);
+}
+
+unsafe fn move_by(hwnd: HWND, dx: i32, dy: i32) {
+    let Some(rect) = window::rect(hwnd) else {
+        return;
+    };
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_NOSIZE;
+
+    let _ = SetWindowPos(hwnd, None, rect.left + dx, rect.top + dy, 0, 0, flags);
+}
+
+/// Grow or shrink the window, keeping its top-left corner in place
+unsafe fn resize_by(hwnd: HWND, dw: i32, dh: i32) {
+    let mut rect = RECT::default();
+
+    if GetWindowRect(hwnd, &mut rect).is_err() {
+        return;
+    }
+
+    let w = (rect.right - rect.left + dw).max(1);
+    let h = (rect.bottom - rect.top + dh).max(1);
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_NOMOVE;
+
+    let _ = SetWindowPos(hwnd, None, 0, 0, w, h, flags);
+}
+
+/// Restore the window if minimized and bring it to the foreground
+unsafe fn focus_window(hwnd: HWND) {
+    if IsIconic(hwnd).as_bool() {
+        let _ = ShowWindow(hwnd, SW_RESTORE);
+    }
+
+    let _ = SetForegroundWindow(hwnd);
+}
+
+/// Switch taskbar auto-hide on or off. Returns false if there is no taskbar.
+unsafe fn toggle_taskbar_autohide() -> bool {
+    let Ok(taskbar) = FindWindowW(w!("Shell_TrayWnd"), None) else {
+        return false;
+    };
+
+    let mut data = APPBARDATA {
+        cbSize: size_of::<APPBARDATA>() as u32,
+        hWnd: taskbar,
+        ..Default::default()
+    };
+
+    let state = SHAppBarMessage(ABM_GETSTATE, &mut data) as u32;
+
+    data.lParam = LPARAM((state ^ ABS_AUTOHIDE) as isize);
+    SHAppBarMessage(ABM_SETSTATE, &mut data);
+
+    true
+}
+
+/// Ask whether to end the process of a window that won't close, on another
+/// thread so that keybinds keep working while the question is up. The
+/// process is opened before asking, so that the handle keeps referring to it
+/// even if it exits and its id is reused meanwhile.
+unsafe fn offer_terminate(hwnd: HWND, pid: u32) {
+    let process = match OpenProcess(PROCESS_TERMINATE | PROCESS_SYNCHRONIZE, false, pid) {
+        Ok(process) => process,
+        Err(err) => {
+            eprintln!("Failed to open process {pid}: {err}");
+            return;
+        }
+    };
+
+    let title = window::title(hwnd);
+    let exe = window::process_name(hwnd).unwrap_or_else(|| format!("process {pid}"));
+    // Handles aren't `Send`, so it crosses over as a number
+    let process = process.0 as isize;
+
+    thread::spawn(move || {
+        let process = HANDLE(process as *mut _);
+        let body =
+            format!("\"{title}\" hasn't closed. End {exe}? Anything unsaved in it will be lost.");
+
+        if report::confirm("winmgr", &body) {
+            unsafe { terminate(process, pid) };
+        }
+
+        let _ = unsafe { CloseHandle(process) };
+    });
+}
+
+/// End the process unless it has exited while the question was up
+unsafe fn terminate(process: HANDLE, pid: u32) {
+    if WaitForSingleObject(process, 0) == WAIT_OBJECT_0 {
+        return;
+    }
+
+    if let Err(err) = TerminateProcess(process, 1) {
+        eprintln!("Failed to end process {pid}: {err}");
+    }
+}
+
+/// Move the mouse cursor to the middle of a window
+unsafe fn warp_cursor(hwnd: HWND) {
+    if let Some(rect) = window::rect(hwnd) {
+        let _ = SetCursorPos((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2);
+    }
+}
+
+/// Start a program, expanding environment variables in it, its arguments and
+/// its directory
+fn launch(program: &str, args: &[String], cwd: Option<&str>) {
+    let mut command = process::Command::new(expand::expand(program));
+    command.args(args.iter().map(|arg| expand::expand(arg)));
+
+    if let Some(cwd) = cwd {
+        command.current_dir(expand::expand(cwd));
+    }
+
+    if let Err(err) = command.spawn() {
+        eprintln!("Failed to run {program}: {err}");
+    }
+}
+
+unsafe fn focus_or_launch(exe: &str, args: &[String]) {
+    let window = WindowMatch {
+        exe: Some(exe.to_owned()),
+        class: None,
+        title: None,
+    };
+
+    match window::app_windows()
+        .into_iter()
+        .find(|&hwnd| window.matches(hwnd))
+    {
+        Some(hwnd) => focus_window(hwnd),
+        None => launch(exe, args, None),
+    }
+}
+
+unsafe fn focus_neighbour(hwnd: HWND, direction: Direction) {
+    if let Some(neighbour) = window::neighbour(hwnd, direction) {
+        focus_window(neighbour);
+    }
+}
+
+unsafe fn swap_with_neighbour(hwnd: HWND, direction: Direction) {
+    let Some(neighbour) = window::neighbour(hwnd, direction) else {
+        return;
+    };
+
+    let (Some(a), Some(b)) = (window::rect(hwnd), window::rect(neighbour)) else {
+        return;
+    };
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE | SWP_FRAMECHANGED;
+
+    for (hwnd, rect) in [(hwnd, b), (neighbour, a)] {
+        let (w, h) = (rect.right - rect.left, rect.bottom - rect.top);
+        let _ = SetWindowPos(hwnd, None, rect.left, rect.top, w, h, flags);
+    }
+}
+
+/// Change the style of a window and have it redraw its frame
+unsafe fn set_style(hwnd: HWND, style: WINDOW_STYLE) {
+    SetWindowLongPtrW(hwnd, GWL_STYLE, style.0 as isize);
+
+    let flags: SET_WINDOW_POS_FLAGS =
+        SWP_FRAMECHANGED | SWP_NOMOVE | SWP_NOSIZE | SWP_NOZORDER | SWP_NOACTIVATE;
+
+    let _ = SetWindowPos(hwnd, None, 0, 0, 0, 0, flags);
+}
+
+unsafe fn toggle_always_on_top(hwnd: HWND) {
+    let ex_style = WINDOW_EX_STYLE(GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32);
+
+    set_always_on_top(hwnd, !ex_style.contains(WS_EX_TOPMOST));
+}
+
+unsafe fn set_always_on_top(hwnd: HWND, on: bool) {
+    let insert_after = match on {
+        true => HWND_TOPMOST,
+        false => HWND_NOTOPMOST,
+    };
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOMOVE | SWP_NOSIZE | SWP_NOACTIVATE;
+
+    let _ = SetWindowPos(hwnd, Some(insert_after), 0, 0, 0, 0, flags);
+}
+
+/// Opacity of a window in percent
+unsafe fn opacity(hwnd: HWND) -> u8 {
+    let ex_style = WINDOW_EX_STYLE(GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32);
+
+    if !ex_style.contains(WS_EX_LAYERED) {
+        return 100;
+    }
+
+    let mut alpha = 255;
+    let mut flags = LAYERED_WINDOW_ATTRIBUTES_FLAGS(0);
+
+    if GetLayeredWindowAttributes(hwnd, None, Some(&mut alpha), Some(&mut flags)).is_err()
+        || !flags.contains(LWA_ALPHA)
+    {
+        return 100;
+    }
+
+    ((alpha as u32 * 100 + 127) / 255) as u8
+}
+
+/// Set the opacity of a window in percent, making it a layered window first
+/// if need be
+unsafe fn set_opacity(hwnd: HWND, percent: u8) {
+    let percent = percent.clamp(MIN_OPACITY, 100);
+    let ex_style = WINDOW_EX_STYLE(GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32);
+
+    if !ex_style.contains(WS_EX_LAYERED) {
+        SetWindowLongPtrW(hwnd, GWL_EXSTYLE, (ex_style | WS_EX_LAYERED).0 as isize);
+    }
+
+    let alpha = (percent as u32 * 255 / 100) as u8;
+
+    if let Err(err) = SetLayeredWindowAttributes(hwnd, COLORREF(0), alpha, LWA_ALPHA) {
+        eprintln!("Failed to set window opacity: {err}");
+    }
+}
+
+/// Report keybinds that can never run because an earlier keybind with the same
+/// combo and `when` clause takes precedence, and duplicate keys within chords
+fn find_conflicts(keybinds: &[KeyBind]) -> Vec<String> {
+    let mut conflicts = Vec::new();
+
+    for (index, keybind) in keybinds.iter().enumerate() {
+        let shadowed_by = keybinds[..index].iter().position(|earlier| {
+            earlier.combo_id().is_some()
+                && earlier.combo_id() == keybind.combo_id()
+                && earlier.when == keybind.when
+        });
+
+        if let Some(earlier) = shadowed_by {
+            conflicts.push(format!(
+                "Keybind {index} ({}) conflicts with keybind {earlier} and will never run",
+                keybind.combo()
+            ));
+        }
+
+        for (offset, bind) in keybind.chord.iter().enumerate() {
+            let combo = parse_combo(&bind.modifiers, &bind.key).ok();
+
+            let duplicate = combo.is_some()
+                && keybind.chord[..offset]
+                    .iter()
+                    .any(|earlier| parse_combo(&earlier.modifiers, &earlier.key).ok() == combo);
+
+            if duplicate {
+                conflicts.push(format!(
+                    "Chord key {offset} ({}) of keybind {index} is bound more than once",
+                    describe_combo(&bind.modifiers, &bind.key)
+                ));
+            }
+        }
+    }
+
+    conflicts
+}
+
+/// Human-readable combo such as `Win+Shift+Left`. Invalid modifiers are
+/// shown as written, since they can't be described.
+fn describe_combo(modifiers: &[HexModifier], key: &HexVirtualKey) -> String {
+    let Ok(mods) = parse_modifiers(modifiers) else {
+        let mut parts: Vec<&str> = modifiers.iter().map(|m| m.0.as_str()).collect();
+        parts.push(&key.0);
+        return parts.join("+");
+    };
+
+    let mut combo = String::new();
+
+    for (flag, name) in [
+        (MOD_WIN, "Win"),
+        (MOD_CONTROL, "Ctrl"),
+        (MOD_ALT, "Alt"),
+        (MOD_SHIFT, "Shift"),
+    ] {
+        if mods.contains(flag) {
+            combo.push_str(name);
+            combo.push('+');
+        }
+    }
+
+    combo.push_str(&key.0);
+    combo
+}
+
+/// The modifiers combined, or which one isn't a real one
+fn parse_modifiers(modifiers: &[HexModifier]) -> Result<HOT_KEY_MODIFIERS, String> {
+    let mut mods = HOT_KEY_MODIFIERS(0);
+
+    for modifier in modifiers {
+        mods |= modifier
+            .parse()
+            .ok_or_else(|| format!("unknown modifier \"{}\"", modifier.0))?;
+    }
+
+    Ok(mods)
+}
+
+/// Modifiers and virtual key, or why they aren't real ones
+fn parse_combo(
+    modifiers: &[HexModifier],
+    key: &HexVirtualKey,
+) -> Result<(HOT_KEY_MODIFIERS, VIRTUAL_KEY), String> {
+    Ok((parse_modifiers(modifiers)?, key.parse()?))
 }
@@ -0,0 +1,192 @@
+//! Upgrading configs written for older versions of winmgr.
+//!
+//! Migrations work on the parsed document rather than on `Config`, since an
+//! old config may no longer deserialize at all.
+
+use serde_json::{Map, Value};
+
+/// Version written by this build of winmgr
+pub const CONFIG_VERSION: u32 = 1;
+
+/// Migration from version `n` to `n + 1` is at index `n`
+const MIGRATIONS: [fn(&mut Value); CONFIG_VERSION as usize] = [unversioned];
+
+/// Version of the config, where configs from before versioning are 0
+pub fn version(config: &Value) -> u32 {
+    config
+        .get("version")
+        .and_then(Value::as_u64)
+        .map_or(0, |version| version as u32)
+}
+
+/// Bring the config up to the current version
+pub fn migrate(config: &mut Value) -> Result<(), String> {
+    let from = version(config);
+
+    if from > CONFIG_VERSION {
+        return Err(format!(
+            "The config is version {from}, but this winmgr only understands up to version {CONFIG_VERSION}"
+        ));
+    }
+
+    for migration in &MIGRATIONS[from as usize..] {
+        migration(config);
+    }
+
+    if let Some(config) = config.as_object_mut() {
+        config.insert("version".into(), CONFIG_VERSION.into());
+    }
+
+    Ok(())
+}
+
+/// Bindings used to take a `layout`, which is now spelled `action`, and could
+/// give a single `modifier`, which is now always a list of `modifiers`
+fn unversioned(config: &mut Value) {
+    let rename = |bind: &mut Map<String, Value>| {
+        if !bind.contains_key("action")
+            && let Some(layout) = bind.remove("layout")
+        {
+            bind.insert("action".into(), layout);
+        }
+
+        if !bind.contains_key("modifiers")
+            && let Some(modifier) = bind.remove("modifier")
+        {
+            let modifiers = match modifier {
+                Value::Array(_) => modifier,
+                _ => Value::Array(vec![modifier]),
+            };
+
+            bind.insert("modifiers".into(), modifiers);
+        }
+    };
+
+    let mut lists: Vec<&mut Value> = Vec::new();
+
+    if let Some(config) = config.as_object_mut() {
+        for (key, value) in config.iter_mut() {
+            match key.as_str() {
+                "keybinds" | "mousebinds" | "gestures" => lists.push(value),
+                "layers" => lists.extend(
+                    value
+                        .as_object_mut()
+                        .into_iter()
+                        .flat_map(|layers| layers.values_mut()),
+                ),
+                _ => {}
+            }
+        }
+    }
+
+    for bind in lists
+        .into_iter()
+        .filter_map(Value::as_array_mut)
+        .flatten()
+        .filter_map(Value::as_object_mut)
+    {
+        rename(bind);
+
+        if let Some(chord) = bind.get_mut("chord").and_then(Value::as_array_mut) {
+            chord
+                .iter_mut()
+                .filter_map(Value::as_object_mut)
+                .for_each(rename);
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use serde_json::json;
+
+    use super::*;
+
+    #[test]
+    fn unversioned_config_is_version_0() {
+        assert_eq!(version(&json!({ "keybinds": [] })), 0);
+        assert_eq!(version(&json!({ "version": 1 })), 1);
+    }
+
+    #[test]
+    fn renames_layout_to_action() {
+        let mut config = json!({
+            "keybinds": [
+                {
+                    "modifiers": ["0x8"],
+                    "key": "K",
+                    "chord": [{ "key": "L", "layout": "LeftHalf" }]
+                },
+                { "modifiers": ["0x8"], "key": "Left", "layout": "LeftHalf" }
+            ],
+            "layers": {
+                "resize": [{ "modifiers": [], "key": "Up", "layout": "TopHalf" }]
+            },
+            "mousebinds": [{ "modifiers": ["0x8"], "button": "middle", "layout": "Maximize" }]
+        });
+
+        migrate(&mut config).unwrap();
+
+        assert_eq!(config["keybinds"][0]["chord"][0]["action"], "LeftHalf");
+        assert_eq!(config["keybinds"][1]["action"], "LeftHalf");
+        assert_eq!(config["layers"]["resize"][0]["action"], "TopHalf");
+        assert_eq!(config["mousebinds"][0]["action"], "Maximize");
+        assert!(config["keybinds"][1].get("layout").is_none());
+    }
+
+    #[test]
+    fn keeps_action_over_layout() {
+        let mut config = json!({
+            "keybinds": [{ "modifiers": [], "key": "A", "action": "Minimize", "layout": "LeftHalf" }]
+        });
+
+        migrate(&mut config).unwrap();
+
+        assert_eq!(config["keybinds"][0]["action"], "Minimize");
+    }
+
+    #[test]
+    fn turns_single_modifier_into_list() {
+        let mut config = json!({
+            "keybinds": [
+                { "modifier": "0x8", "key": "Left", "action": "LeftHalf" },
+                { "modifier": ["0x8", "0x1"], "key": "Right", "action": "RightHalf" }
+            ]
+        });
+
+        migrate(&mut config).unwrap();
+
+        assert_eq!(config["keybinds"][0]["modifiers"], json!(["0x8"]));
+        assert_eq!(config["keybinds"][1]["modifiers"], json!(["0x8", "0x1"]));
+        assert!(config["keybinds"][0].get("modifier").is_none());
+    }
+
+    #[test]
+    fn sets_current_version() {
+        let mut config = json!({});
+
+        migrate(&mut config).unwrap();
+
+        assert_eq!(version(&config), CONFIG_VERSION);
+    }
+
+    #[test]
+    fn leaves_current_config_alone() {
+        let original = json!({
+            "version": CONFIG_VERSION,
+            "keybinds": [{ "modifiers": ["0x8"], "key": "Left", "action": "LeftHalf" }]
+        });
+        let mut config = original.clone();
+
+        migrate(&mut config).unwrap();
+
+        assert_eq!(config, original);
+    }
+
+    #[test]
+    fn rejects_newer_version() {
+        let mut config = json!({ "version": CONFIG_VERSION + 1 });
+
+        assert!(migrate(&mut config).is_err());
+    }
+}
@@ -0,0 +1,30 @@
+//! Portable mode, for running off a USB stick without touching the user
+//! profile: the config and log live next to the executable.
+//!
+//! Portable mode is on when a `winmgr.json` or `winmgr.toml` sits beside the
+//! executable, or when it's asked for with `--portable`.
+
+use std::{
+    env,
+    path::PathBuf,
+    sync::atomic::{AtomicBool, Ordering},
+};
+
+static FORCED: AtomicBool = AtomicBool::new(false);
+
+/// Turn portable mode on whether or not there is a config beside the executable
+pub fn force() {
+    FORCED.store(true, Ordering::Relaxed);
+}
+
+/// Directory of the executable, if running in portable mode
+pub fn dir() -> Option<PathBuf> {
+    let exe = env::current_exe().ok()?;
+    let dir = exe.parent()?.to_path_buf();
+
+    let has_config = ["winmgr.json", "winmgr.toml"]
+        .iter()
+        .any(|name| dir.join(name).exists());
+
+    (FORCED.load(Ordering::Relaxed) || has_config).then_some(dir)
+}
@@ -0,0 +1,94 @@
+//! Surfacing problems to the user. Release builds have no console, so
+//! anything worth knowing about goes to a log file and a message box.
+
+use std::{
+    fs::OpenOptions,
+    io::Write,
+    path::PathBuf,
+    thread::{self, JoinHandle},
+};
+
+use directories::UserDirs;
+use windows::{
+    Win32::UI::WindowsAndMessaging::{
+        IDYES, MB_DEFBUTTON2, MB_ICONWARNING, MB_OK, MB_SETFOREGROUND, MB_TOPMOST, MB_YESNO,
+        MessageBoxW,
+    },
+    core::HSTRING,
+};
+
+pub fn log_path() -> Option<PathBuf> {
+    if let Some(dir) = crate::portable::dir() {
+        return Some(dir.join("winmgr.log"));
+    }
+
+    let dirs = UserDirs::new()?;
+
+    Some(dirs.home_dir().join("winmgr.log"))
+}
+
+/// Append lines to the log file, and echo them to stderr for debug builds
+pub fn log(lines: &[String]) {
+    for line in lines {
+        eprintln!("{line}");
+    }
+
+    let Some(path) = log_path() else {
+        return;
+    };
+
+    let Ok(mut file) = OpenOptions::new().create(true).append(true).open(path) else {
+        return;
+    };
+
+    for line in lines {
+        let _ = writeln!(file, "{line}");
+    }
+}
+
+/// Show a warning message box without blocking the caller. Join the handle
+/// to keep the box up when the process is about to exit.
+pub fn notify(title: &str, body: &str) -> JoinHandle<()> {
+    let title = HSTRING::from(title);
+    let body = HSTRING::from(body);
+
+    thread::spawn(move || unsafe {
+        MessageBoxW(
+            None,
+            &body,
+            &title,
+            MB_OK | MB_ICONWARNING | MB_SETFOREGROUND,
+        );
+    })
+}
+
+/// Ask a yes or no question, with no as the default. Blocks until answered.
+pub fn confirm(title: &str, body: &str) -> bool {
+    let answer = unsafe {
+        MessageBoxW(
+            None,
+            &HSTRING::from(body),
+            &HSTRING::from(title),
+            MB_YESNO | MB_DEFBUTTON2 | MB_ICONWARNING | MB_SETFOREGROUND | MB_TOPMOST,
+        )
+    };
+
+    answer == IDYES
+}
+
+/// Log the problems and show them in a single message box
+pub fn problems(title: &str, problems: &[String]) -> Option<JoinHandle<()>> {
+    if problems.is_empty() {
+        return None;
+    }
+
+    log(problems);
+
+    let mut body = problems.join("\n");
+
+    if let Some(path) = log_path() {
+        body.push_str(&format!("\n\nSee {}", path.display()));
+    }
+
+    Some(notify(title, &body))
+}
@@ -0,0 +1,240 @@
+//! `winmgr snapshot`, saving where every open window is and putting them back
+//! there later, e.g. after docking a laptop.
+//!
+//! Windows don't keep their handles across restarts, so a saved window is
+//! matched to an open one of the same process, preferring one with the same
+//! title and then one with the same class. Positions are kept relative to the
+//! monitor the window was on, so they survive the monitor moving or changing
+//! resolution.
+
+use std::{collections::HashSet, error::Error, fs, path::PathBuf};
+
+use directories::BaseDirs;
+use serde::{Deserialize, Serialize};
+use windows::Win32::{
+    Foundation::HWND,
+    UI::WindowsAndMessaging::{
+        IsIconic, IsZoomed, SET_WINDOW_POS_FLAGS, SW_MAXIMIZE, SW_RESTORE, SWP_NOACTIVATE,
+        SWP_NOZORDER, SetWindowPos, ShowWindow,
+    },
+};
+
+use crate::{corners, monitor_info, monitor_number, nth_monitor_info, portable, window};
+
+#[derive(Debug, Serialize, Deserialize)]
+struct SavedWindow {
+    exe: String,
+    title: String,
+    class: String,
+    /// Window as `[left, top, right, bottom]`
+    rect: [i32; 4],
+    maximized: bool,
+    /// Number of the monitor the window was on, and that monitor's rect
+    #[serde(default, skip_serializing_if = "Option::is_none")]
+    monitor: Option<(u32, [i32; 4])>,
+}
+
+/// How closely an open window resembles a saved one, best first
+#[derive(Clone, Copy)]
+enum Likeness {
+    SameTitle,
+    SameClass,
+    SameProcess,
+}
+
+pub fn save(name: &str) -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let path = snapshot_path(name)?;
+
+    let windows: Vec<SavedWindow> = unsafe {
+        window::app_windows()
+            .into_iter()
+            .filter(|&hwnd| !IsIconic(hwnd).as_bool())
+            .filter_map(|hwnd| saved_window(hwnd))
+            .collect()
+    };
+
+    if let Some(dir) = path.parent() {
+        fs::create_dir_all(dir)?;
+    }
+
+    fs::write(&path, serde_json::to_string_pretty(&windows)?)?;
+
+    println!("Saved {} windows to {}", windows.len(), path.display());
+
+    Ok(())
+}
+
+pub fn restore(name: &str) -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let path = snapshot_path(name)?;
+
+    let text = fs::read_to_string(&path)
+        .map_err(|err| format!("Failed to read snapshot {name}: {err}"))?;
+    let saved: Vec<SavedWindow> = serde_json::from_str(&text)?;
+
+    let mut matched: Vec<Option<HWND>> = vec![None; saved.len()];
+    let mut used: HashSet<isize> = HashSet::new();
+
+    unsafe {
+        let open: Vec<(HWND, Option<String>, String, String)> = window::app_windows()
+            .into_iter()
+            .map(|hwnd| {
+                let exe = window::process_name(hwnd);
+                (hwnd, exe, window::title(hwnd), window::class_name(hwnd))
+            })
+            .collect();
+
+        // Match closely resembling windows first, so that a loose match
+        // doesn't take a window that a later saved one fits exactly
+        for likeness in [
+            Likeness::SameTitle,
+            Likeness::SameClass,
+            Likeness::SameProcess,
+        ] {
+            for (index, entry) in saved.iter().enumerate() {
+                if matched[index].is_some() {
+                    continue;
+                }
+
+                let found = open.iter().find(|(hwnd, exe, title, class)| {
+                    !used.contains(&(hwnd.0 as isize))
+                        && exe
+                            .as_deref()
+                            .is_some_and(|exe| exe.eq_ignore_ascii_case(&entry.exe))
+                        && match likeness {
+                            Likeness::SameTitle => *title == entry.title,
+                            Likeness::SameClass => *class == entry.class,
+                            Likeness::SameProcess => true,
+                        }
+                });
+
+                if let Some((hwnd, ..)) = found {
+                    used.insert(hwnd.0 as isize);
+                    matched[index] = Some(*hwnd);
+                }
+            }
+        }
+
+        for (entry, hwnd) in saved.iter().zip(&matched) {
+            if let Some(hwnd) = hwnd {
+                place(*hwnd, entry);
+            }
+        }
+    }
+
+    let restored = matched.iter().flatten().count();
+    println!("Restored {restored} of {} windows", saved.len());
+
+    for (entry, hwnd) in saved.iter().zip(&matched) {
+        if hwnd.is_none() {
+            println!("  Not open: {} ({})", entry.title, entry.exe);
+        }
+    }
+
+    Ok(())
+}
+
+pub fn list(json: bool) -> Result<(), Box<dyn Error>> {
+    crate::attach_console();
+
+    let names: Vec<String> = match fs::read_dir(snapshot_dir()?) {
+        Ok(entries) => entries
+            .flatten()
+            .map(|entry| entry.path())
+            .filter(|path| path.extension().is_some_and(|ext| ext == "json"))
+            .filter_map(|path| Some(path.file_stem()?.to_string_lossy().into_owned()))
+            .collect(),
+        Err(_) => Vec::new(),
+    };
+
+    if json {
+        println!("{}", serde_json::to_string_pretty(&names)?);
+        return Ok(());
+    }
+
+    for name in names {
+        println!("{name}");
+    }
+
+    Ok(())
+}
+
+unsafe fn saved_window(hwnd: HWND) -> Option<SavedWindow> {
+    let rect = window::rect(hwnd)?;
+    let monitor =
+        monitor_info(hwnd).and_then(|mi| Some((monitor_number(&mi)?, corners(mi.rcMonitor))));
+
+    Some(SavedWindow {
+        exe: window::process_name(hwnd)?,
+        title: window::title(hwnd),
+        class: window::class_name(hwnd),
+        rect: corners(rect),
+        maximized: IsZoomed(hwnd).as_bool(),
+        monitor,
+    })
+}
+
+unsafe fn place(hwnd: HWND, saved: &SavedWindow) {
+    let [left, top, right, bottom] = rect_now(saved);
+
+    if IsZoomed(hwnd).as_bool() || IsIconic(hwnd).as_bool() {
+        let _ = ShowWindow(hwnd, SW_RESTORE);
+    }
+
+    let flags: SET_WINDOW_POS_FLAGS = SWP_NOZORDER | SWP_NOACTIVATE;
+
+    let _ = SetWindowPos(hwnd, None, left, top, right - left, bottom - top, flags);
+
+    // Maximizes onto the monitor the window was just moved to
+    if saved.maximized {
+        let _ = ShowWindow(hwnd, SW_MAXIMIZE);
+    }
+}
+
+/// Where a saved window goes on the monitors as they are now: scaled from its
+/// monitor then onto the same monitor now, or where it was if that's gone
+unsafe fn rect_now(saved: &SavedWindow) -> [i32; 4] {
+    let Some((number, [from_left, from_top, from_right, from_bottom])) = saved.monitor else {
+        return saved.rect;
+    };
+
+    let Some(mi) = nth_monitor_info(number) else {
+        return saved.rect;
+    };
+
+    let to = mi.rcMonitor;
+    let scale_x = (to.right - to.left) as f32 / (from_right - from_left).max(1) as f32;
+    let scale_y = (to.bottom - to.top) as f32 / (from_bottom - from_top).max(1) as f32;
+
+    let [left, top, right, bottom] = saved.rect;
+
+    [
+        to.left + ((left - from_left) as f32 * scale_x).round() as i32,
+        to.top + ((top - from_top) as f32 * scale_y).round() as i32,
+        to.left + ((right - from_left) as f32 * scale_x).round() as i32,
+        to.top + ((bottom - from_top) as f32 * scale_y).round() as i32,
+    ]
+}
+
+/// `snapshots` in the config directory, or next to the executable in portable
+/// mode
+fn snapshot_dir() -> Result<PathBuf, Box<dyn Error>> {
+    if let Some(dir) = portable::dir() {
+        return Ok(dir.join("snapshots"));
+    }
+
+    let base = BaseDirs::new().ok_or("Failed to get user config directory")?;
+
+    Ok(base.config_dir().join("winmgr").join("snapshots"))
+}
+
+fn snapshot_path(name: &str) -> Result<PathBuf, Box<dyn Error>> {
+    if name.is_empty() || name.contains(['/', '\\', ':', '.']) {
+        return Err(format!("Invalid snapshot name {name}").into());
+    }
+
+    Ok(snapshot_dir()?.join(format!("{name}.json")))
+}
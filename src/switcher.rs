@@ -0,0 +1,323 @@
+//! `Switcher`, a popup listing the open windows that narrows down as part of
+//! a title or process name is typed. Up and Down pick a window, Enter focuses
+//! it, and Escape or clicking elsewhere closes the popup.
+//!
+//! The popup is a plain window painted with GDI. It lives on the main thread
+//! like everything else, so its state is kept in a thread local.
+
+use std::cell::RefCell;
+
+use windows::{
+    Win32::{
+        Foundation::{COLORREF, HWND, LPARAM, LRESULT, RECT, WPARAM},
+        Graphics::Gdi::{
+            BeginPaint, COLOR_HIGHLIGHT, COLOR_HIGHLIGHTTEXT, COLOR_WINDOW, COLOR_WINDOWTEXT,
+            DEFAULT_GUI_FONT, DT_END_ELLIPSIS, DT_NOPREFIX, DT_SINGLELINE, DT_VCENTER, DrawTextW,
+            EndPaint, FillRect, GetStockObject, GetSysColor, GetSysColorBrush, HDC, InvalidateRect,
+            PAINTSTRUCT, SelectObject, SetBkMode, SetTextColor, TRANSPARENT,
+        },
+        System::LibraryLoader::GetModuleHandleW,
+        UI::{
+            Input::KeyboardAndMouse::{VIRTUAL_KEY, VK_BACK, VK_DOWN, VK_ESCAPE, VK_RETURN, VK_UP},
+            WindowsAndMessaging::{
+                CreateWindowExW, DefWindowProcW, DestroyWindow, GetClientRect, GetForegroundWindow,
+                IDC_ARROW, LoadCursorW, RegisterClassW, SW_SHOW, SetForegroundWindow, ShowWindow,
+                WA_INACTIVE, WM_ACTIVATE, WM_CHAR, WM_KEYDOWN, WM_PAINT, WNDCLASSW, WS_BORDER,
+                WS_EX_TOOLWINDOW, WS_EX_TOPMOST, WS_POPUP,
+            },
+        },
+    },
+    core::{PCWSTR, w},
+};
+
+use crate::window;
+
+const CLASS_NAME: PCWSTR = w!("winmgr_switcher");
+
+const WIDTH: i32 = 640;
+const ROW_HEIGHT: i32 = 26;
+const PADDING: i32 = 8;
+
+/// Windows listed at once, below the line with the typed text
+const MAX_ROWS: usize = 12;
+
+struct Entry {
+    hwnd: HWND,
+    /// Title and process as shown
+    text: String,
+    /// Title and process lowercased, for matching
+    key: String,
+}
+
+struct Switcher {
+    hwnd: HWND,
+    entries: Vec<Entry>,
+    query: String,
+    /// Indexes into `entries` matching `query`, best first
+    matches: Vec<usize>,
+    /// Position in `matches`
+    selected: usize,
+}
+
+thread_local! {
+    static SWITCHER: RefCell<Option<Switcher>> = const { RefCell::new(None) };
+}
+
+impl Switcher {
+    fn refilter(&mut self) {
+        let query = self.query.to_lowercase();
+
+        let mut scored: Vec<(u8, usize)> = self
+            .entries
+            .iter()
+            .enumerate()
+            .filter_map(|(index, entry)| Some((score(&entry.key, &query)?, index)))
+            .collect();
+
+        // Ties stay in z-order, most recently used first
+        scored.sort();
+
+        self.matches = scored.into_iter().map(|(_, index)| index).collect();
+        self.selected = 0;
+    }
+}
+
+/// Open the switcher over `windows`, given in z-order. The window after the
+/// focused one is selected to begin with, as with Alt+Tab.
+pub unsafe fn open(windows: Vec<HWND>) {
+    close();
+
+    let entries: Vec<Entry> = windows
+        .into_iter()
+        .map(|hwnd| {
+            let title = window::title(hwnd);
+            let exe = window::process_name(hwnd).unwrap_or_default();
+
+            Entry {
+                hwnd,
+                key: format!("{title} {exe}").to_lowercase(),
+                text: format!("{title}  ({exe})"),
+            }
+        })
+        .collect();
+
+    let Ok(instance) = GetModuleHandleW(None) else {
+        return;
+    };
+
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(wnd_proc),
+        hInstance: instance.into(),
+        lpszClassName: CLASS_NAME,
+        hCursor: LoadCursorW(None, IDC_ARROW).unwrap_or_default(),
+        ..Default::default()
+    };
+
+    // Fails harmlessly once the class is registered
+    RegisterClassW(&class);
+
+    let Some(mi) = crate::monitor_info(GetForegroundWindow()) else {
+        return;
+    };
+
+    let work = mi.rcWork;
+    let height = (MAX_ROWS as i32 + 1) * ROW_HEIGHT + 2 * PADDING;
+
+    let hwnd = match CreateWindowExW(
+        WS_EX_TOPMOST | WS_EX_TOOLWINDOW,
+        CLASS_NAME,
+        w!("winmgr switcher"),
+        WS_POPUP | WS_BORDER,
+        (work.left + work.right - WIDTH) / 2,
+        work.top + (work.bottom - work.top - height) / 3,
+        WIDTH,
+        height,
+        None,
+        None,
+        Some(instance.into()),
+        None,
+    ) {
+        Ok(hwnd) => hwnd,
+        Err(err) => {
+            eprintln!("Failed to open switcher: {err}");
+            return;
+        }
+    };
+
+    let mut switcher = Switcher {
+        hwnd,
+        entries,
+        query: String::new(),
+        matches: Vec::new(),
+        selected: 0,
+    };
+
+    switcher.refilter();
+    switcher.selected = 1.min(switcher.matches.len().saturating_sub(1));
+
+    // Set before showing, since showing sends messages to `wnd_proc`
+    SWITCHER.set(Some(switcher));
+
+    let _ = ShowWindow(hwnd, SW_SHOW);
+    let _ = SetForegroundWindow(hwnd);
+}
+
+/// Close the switcher if it's open
+pub unsafe fn close() {
+    // Taken out first, since destroying the window sends messages to `wnd_proc`
+    if let Some(switcher) = SWITCHER.take() {
+        let _ = DestroyWindow(switcher.hwnd);
+    }
+}
+
+/// Close the switcher and focus the selected window
+unsafe fn choose() {
+    let Some(switcher) = SWITCHER.take() else {
+        return;
+    };
+
+    let _ = DestroyWindow(switcher.hwnd);
+
+    if let Some(&index) = switcher.matches.get(switcher.selected) {
+        crate::focus_window(switcher.entries[index].hwnd);
+    }
+}
+
+/// Change the open switcher and repaint it
+unsafe fn update(hwnd: HWND, change: impl FnOnce(&mut Switcher)) {
+    SWITCHER.with_borrow_mut(|switcher| {
+        if let Some(switcher) = switcher {
+            change(switcher);
+        }
+    });
+
+    let _ = InvalidateRect(Some(hwnd), None, true);
+}
+
+/// How well `text` matches `query`, lower is better: 0 when it contains the
+/// query as is, 1 when it contains the query's characters in order
+fn score(text: &str, query: &str) -> Option<u8> {
+    if text.contains(query) {
+        return Some(0);
+    }
+
+    let mut chars = text.chars();
+
+    query.chars().all(|q| chars.any(|c| c == q)).then_some(1)
+}
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    match message {
+        WM_PAINT => {
+            paint(hwnd);
+            return LRESULT(0);
+        }
+        WM_CHAR => {
+            // Enter, Escape and Backspace arrive as control characters too
+            if let Some(ch) = char::from_u32(wparam.0 as u32).filter(|ch| !ch.is_control()) {
+                update(hwnd, |switcher| {
+                    switcher.query.push(ch);
+                    switcher.refilter();
+                });
+            }
+
+            return LRESULT(0);
+        }
+        WM_KEYDOWN => {
+            match VIRTUAL_KEY(wparam.0 as u16) {
+                VK_ESCAPE => close(),
+                VK_RETURN => choose(),
+                VK_UP => update(hwnd, |switcher| {
+                    switcher.selected = switcher.selected.saturating_sub(1);
+                }),
+                VK_DOWN => update(hwnd, |switcher| {
+                    let last = switcher.matches.len().saturating_sub(1);
+                    switcher.selected = (switcher.selected + 1).min(last);
+                }),
+                VK_BACK => update(hwnd, |switcher| {
+                    switcher.query.pop();
+                    switcher.refilter();
+                }),
+                _ => {}
+            }
+
+            return LRESULT(0);
+        }
+        WM_ACTIVATE if (wparam.0 & 0xFFFF) as u32 == WA_INACTIVE => close(),
+        _ => {}
+    }
+
+    DefWindowProcW(hwnd, message, wparam, lparam)
+}
+
+unsafe fn paint(hwnd: HWND) {
+    let mut ps = PAINTSTRUCT::default();
+    let hdc = BeginPaint(hwnd, &mut ps);
+
+    SWITCHER.with_borrow(|switcher| {
+        if let Some(switcher) = switcher {
+            draw(hdc, hwnd, switcher);
+        }
+    });
+
+    let _ = EndPaint(hwnd, &ps);
+}
+
+unsafe fn draw(hdc: HDC, hwnd: HWND, switcher: &Switcher) {
+    let mut client = RECT::default();
+    let _ = GetClientRect(hwnd, &mut client);
+
+    FillRect(hdc, &client, GetSysColorBrush(COLOR_WINDOW));
+    SelectObject(hdc, GetStockObject(DEFAULT_GUI_FONT));
+    SetBkMode(hdc, TRANSPARENT);
+
+    let row = |n: usize| RECT {
+        left: client.left + PADDING,
+        top: client.top + PADDING + n as i32 * ROW_HEIGHT,
+        right: client.right - PADDING,
+        bottom: client.top + PADDING + (n as i32 + 1) * ROW_HEIGHT,
+    };
+
+    SetTextColor(hdc, COLORREF(GetSysColor(COLOR_WINDOWTEXT)));
+    draw_text(hdc, &format!("> {}", switcher.query), row(0));
+
+    // Scrolled just far enough to keep the selection in view
+    let first = switcher.selected.saturating_sub(MAX_ROWS - 1);
+
+    for (position, &index) in switcher
+        .matches
+        .iter()
+        .enumerate()
+        .skip(first)
+        .take(MAX_ROWS)
+    {
+        let rect = row(position - first + 1);
+
+        let color = match position == switcher.selected {
+            true => {
+                FillRect(hdc, &rect, GetSysColorBrush(COLOR_HIGHLIGHT));
+                COLOR_HIGHLIGHTTEXT
+            }
+            false => COLOR_WINDOWTEXT,
+        };
+
+        SetTextColor(hdc, COLORREF(GetSysColor(color)));
+        draw_text(hdc, &switcher.entries[index].text, rect);
+    }
+}
+
+unsafe fn draw_text(hdc: HDC, text: &str, mut rect: RECT) {
+    let mut text: Vec<u16> = text.encode_utf16().collect();
+
+    DrawTextW(
+        hdc,
+        &mut text,
+        &mut rect,
+        DT_SINGLELINE | DT_VCENTER | DT_END_ELLIPSIS | DT_NOPREFIX,
+    );
+}
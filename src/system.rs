@@ -0,0 +1,99 @@
+//! Hidden top-level window that receives system notifications.
+//!
+//! Broadcasts such as `WM_POWERBROADCAST` are only sent to top-level windows,
+//! not to threads or message-only windows, so the window is a real one that is
+//! never shown. Notifications are forwarded to the message loop as thread messages.
+
+use windows::{
+    Win32::{
+        Foundation::{HWND, LPARAM, LRESULT, WPARAM},
+        System::{
+            LibraryLoader::GetModuleHandleW,
+            RemoteDesktop::{NOTIFY_FOR_THIS_SESSION, WTSRegisterSessionNotification},
+            Threading::GetCurrentThreadId,
+        },
+        UI::WindowsAndMessaging::{
+            CreateWindowExW, DefWindowProcW, PBT_APMRESUMEAUTOMATIC, PBT_APMRESUMESUSPEND,
+            PostThreadMessageW, RegisterClassW, SPI_SETWORKAREA, WINDOW_EX_STYLE, WM_APP,
+            WM_DISPLAYCHANGE, WM_POWERBROADCAST, WM_SETTINGCHANGE, WM_WTSSESSION_CHANGE, WNDCLASSW,
+            WS_OVERLAPPED, WTS_CONSOLE_CONNECT, WTS_REMOTE_CONNECT, WTS_SESSION_UNLOCK,
+        },
+    },
+    core::{Result, w},
+};
+
+/// Posted when the machine resumes from sleep or the session is unlocked or
+/// reconnected, after which hotkeys may have been lost
+pub const WM_APP_RESUMED: u32 = WM_APP + 3;
+
+/// Posted when monitors are connected, disconnected or rearranged, or their
+/// resolution changes
+pub const WM_APP_DISPLAY_CHANGED: u32 = WM_APP + 6;
+
+/// Posted when a monitor's work area changes, such as when the taskbar is
+/// moved, resized or set to auto-hide
+pub const WM_APP_WORK_AREA_CHANGED: u32 = WM_APP + 7;
+
+pub unsafe fn create_window() -> Result<HWND> {
+    let instance = GetModuleHandleW(None)?;
+
+    let class = WNDCLASSW {
+        lpfnWndProc: Some(wnd_proc),
+        hInstance: instance.into(),
+        lpszClassName: w!("winmgr"),
+        ..Default::default()
+    };
+
+    RegisterClassW(&class);
+
+    let hwnd = CreateWindowExW(
+        WINDOW_EX_STYLE(0),
+        w!("winmgr"),
+        w!("winmgr"),
+        WS_OVERLAPPED,
+        0,
+        0,
+        0,
+        0,
+        None,
+        None,
+        Some(instance.into()),
+        None,
+    )?;
+
+    WTSRegisterSessionNotification(hwnd, NOTIFY_FOR_THIS_SESSION)?;
+
+    Ok(hwnd)
+}
+
+unsafe fn post(message: u32) {
+    let _ = PostThreadMessageW(GetCurrentThreadId(), message, WPARAM(0), LPARAM(0));
+}
+
+unsafe extern "system" fn wnd_proc(
+    hwnd: HWND,
+    message: u32,
+    wparam: WPARAM,
+    lparam: LPARAM,
+) -> LRESULT {
+    let event = wparam.0 as u32;
+
+    match message {
+        WM_POWERBROADCAST if matches!(event, PBT_APMRESUMEAUTOMATIC | PBT_APMRESUMESUSPEND) => {
+            post(WM_APP_RESUMED);
+        }
+        WM_WTSSESSION_CHANGE
+            if matches!(
+                event,
+                WTS_SESSION_UNLOCK | WTS_REMOTE_CONNECT | WTS_CONSOLE_CONNECT
+            ) =>
+        {
+            post(WM_APP_RESUMED);
+        }
+        WM_DISPLAYCHANGE => post(WM_APP_DISPLAY_CHANGED),
+        WM_SETTINGCHANGE if event == SPI_SETWORKAREA.0 => post(WM_APP_WORK_AREA_CHANGED),
+        _ => {}
+    }
+
+    DefWindowProcW(hwnd, message, wparam, lparam)
+}
@@ -0,0 +1,474 @@
+//! Checks for `winmgr validate`, covering what can be caught without
+//! registering anything, and of the monitors the config names against those
+//! connected.
+
+use std::{collections::HashMap, iter};
+
+use windows::Win32::{
+    Graphics::Gdi::MONITORINFO,
+    UI::Input::KeyboardAndMouse::{
+        HOT_KEY_MODIFIERS, MOD_ALT, MOD_CONTROL, MOD_NOREPEAT, MOD_SHIFT, MOD_WIN,
+    },
+};
+
+use crate::{
+    Action, BindOptions, Config, CustomLayout, GridLayout, HexModifier, HexVirtualKey, KeyBind,
+    Layout, MonitorTarget, Origin, Unit, WindowMatch, find_conflicts,
+};
+
+/// Every problem found in the config, worded for the user
+pub fn problems(cfg: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    check_keybinds(&cfg.keybinds, None, &mut problems);
+
+    for (name, keybinds) in &cfg.layers {
+        check_keybinds(keybinds, Some(name), &mut problems);
+    }
+
+    for (index, bind) in cfg.mousebinds.iter().enumerate() {
+        let what = format!("Mouse binding {index}");
+
+        check_modifiers(&bind.modifiers, &what, &mut problems);
+        check_action(&bind.action, &what, &mut problems);
+    }
+
+    for (index, gesture) in cfg.gestures.iter().enumerate() {
+        let what = format!("Gesture {index}");
+
+        check_modifiers(
+            std::slice::from_ref(&gesture.double_tap),
+            &what,
+            &mut problems,
+        );
+        check_action(&gesture.action, &what, &mut problems);
+    }
+
+    for (index, exclusion) in cfg.exclusions.iter().enumerate() {
+        let what = format!("Exclusion {index}");
+
+        if exclusion.exe.is_none() && exclusion.class.is_none() && exclusion.title.is_none() {
+            problems.push(format!(
+                "{what} has no exe, class or title, so it excludes every window"
+            ));
+        }
+
+        check_window_match(exclusion, &what, &mut problems);
+    }
+
+    for (index, rule) in cfg.rules.iter().enumerate() {
+        let what = format!("Rule {index}");
+        let window = &rule.window;
+
+        if window.exe.is_none() && window.class.is_none() && window.title.is_none() {
+            problems.push(format!(
+                "{what} has no exe, class or title, so it applies to every window"
+            ));
+        }
+
+        check_window_match(window, &what, &mut problems);
+
+        if let Some(layout) = &rule.layout {
+            check_layout(layout, &what, &mut problems);
+        }
+
+        if let Some(MonitorTarget::Number(0)) = rule.monitor {
+            problems.push(format!("{what} names monitor 0, monitors count from 1"));
+        }
+
+        if rule.opacity.is_some_and(|percent| percent > 100) {
+            problems.push(format!("{what} sets an opacity above 100%"));
+        }
+    }
+
+    problems
+}
+
+/// Keys and modifiers that aren't real ones, worded for the user. winmgr
+/// won't start on or reload to a config with any.
+pub fn combo_problems(cfg: &Config) -> Vec<String> {
+    let mut problems = Vec::new();
+
+    let layers = cfg
+        .layers
+        .iter()
+        .map(|(name, keybinds)| (Some(name.as_str()), keybinds));
+
+    for (layer, keybinds) in iter::once((None, &cfg.keybinds)).chain(layers) {
+        for (index, keybind) in keybinds.iter().enumerate() {
+            let what = keybind_what(index, layer);
+
+            check_modifiers(&keybind.modifiers, &what, &mut problems);
+            check_key(&keybind.key, &what, &mut problems);
+
+            for (offset, bind) in keybind.chord.iter().enumerate() {
+                let what = format!("{what}, chord key {offset}");
+
+                check_modifiers(&bind.modifiers, &what, &mut problems);
+                check_key(&bind.key, &what, &mut problems);
+            }
+        }
+    }
+
+    for (index, bind) in cfg.mousebinds.iter().enumerate() {
+        check_modifiers(
+            &bind.modifiers,
+            &format!("Mouse binding {index}"),
+            &mut problems,
+        );
+    }
+
+    for (index, gesture) in cfg.gestures.iter().enumerate() {
+        check_modifiers(
+            std::slice::from_ref(&gesture.double_tap),
+            &format!("Gesture {index}"),
+            &mut problems,
+        );
+    }
+
+    problems
+}
+
+/// What the config places on screen, each with what names it
+#[derive(Default)]
+struct ScreenRefs {
+    /// Monitors by number or name
+    monitors: Vec<(String, String)>,
+    /// Custom layouts in screen coordinates
+    layouts: Vec<(String, CustomLayout)>,
+}
+
+/// Monitors the config names that aren't connected or that share their
+/// friendly name, and layouts in screen coordinates that lie on none of them,
+/// worded for the user. Checked again whenever monitors come or go.
+pub unsafe fn screen_problems(
+    cfg: &Config,
+    monitors: &[MONITORINFO],
+    names: &HashMap<String, String>,
+) -> Vec<String> {
+    let mut refs = ScreenRefs::default();
+
+    let layers = cfg
+        .layers
+        .iter()
+        .map(|(name, keybinds)| (Some(name.as_str()), keybinds));
+
+    for (layer, keybinds) in iter::once((None, &cfg.keybinds)).chain(layers) {
+        for (index, keybind) in keybinds.iter().enumerate() {
+            let what = keybind_what(index, layer);
+
+            options_refs(&keybind.options, &what, &mut refs);
+
+            if let Some(action) = &keybind.action {
+                action_refs(action, &what, &mut refs);
+            }
+
+            for layout in &keybind.cycle {
+                layout_refs(layout, &what, &mut refs);
+            }
+
+            for bind in &keybind.chord {
+                action_refs(&bind.action, &what, &mut refs);
+            }
+        }
+    }
+
+    for (index, bind) in cfg.mousebinds.iter().enumerate() {
+        let what = format!("Mouse binding {index}");
+
+        options_refs(&bind.options, &what, &mut refs);
+        action_refs(&bind.action, &what, &mut refs);
+    }
+
+    for (index, gesture) in cfg.gestures.iter().enumerate() {
+        let what = format!("Gesture {index}");
+
+        options_refs(&gesture.options, &what, &mut refs);
+        action_refs(&gesture.action, &what, &mut refs);
+    }
+
+    for (index, rule) in cfg.rules.iter().enumerate() {
+        let what = format!("Rule {index}");
+
+        monitor_refs(rule.monitor.as_ref(), &what, &mut refs);
+
+        if let Some(layout) = &rule.layout {
+            layout_refs(layout, &what, &mut refs);
+        }
+    }
+
+    let mut problems = Vec::new();
+
+    for (what, name) in refs.monitors {
+        let matches = monitors
+            .iter()
+            .filter(|mi| crate::monitor_named(mi, &name, names))
+            .count();
+
+        match matches {
+            0 => problems.push(format!(
+                "{what} names monitor {name}, which isn't connected"
+            )),
+            1 => {}
+            _ => problems.push(format!(
+                "{what} names monitor {name}, which is the name of {matches} monitors, so the \
+                 first of them is used"
+            )),
+        }
+    }
+
+    for (what, layout) in refs.layouts {
+        let x = layout.x + layout.w / 2;
+        let y = layout.y + layout.h / 2;
+
+        let on_screen = monitors.iter().any(|mi| {
+            let r = mi.rcMonitor;
+            (r.left..r.right).contains(&x) && (r.top..r.bottom).contains(&y)
+        });
+
+        if !on_screen {
+            problems.push(format!(
+                "{what} has a layout at {},{} that isn't on any connected monitor",
+                layout.x, layout.y
+            ));
+        }
+    }
+
+    for key in cfg.monitors.keys() {
+        if !connected(key) {
+            problems.push(format!(
+                "Monitor settings for {key} match no connected monitor"
+            ));
+        }
+    }
+
+    problems
+}
+
+fn options_refs(options: &BindOptions, what: &str, refs: &mut ScreenRefs) {
+    monitor_refs(options.monitor.as_ref(), what, refs);
+}
+
+fn monitor_refs(monitor: Option<&MonitorTarget>, what: &str, refs: &mut ScreenRefs) {
+    match monitor {
+        Some(MonitorTarget::Number(n)) => refs.monitors.push((what.to_owned(), n.to_string())),
+        Some(MonitorTarget::Name(name)) => refs.monitors.push((what.to_owned(), name.clone())),
+        _ => {}
+    }
+}
+
+fn action_refs(action: &Action, what: &str, refs: &mut ScreenRefs) {
+    match action {
+        Action::ApplyLayout(layout)
+        | Action::PushColumn(layout)
+        | Action::StackOnto(layout)
+        | Action::Scratchpad { layout, .. } => layout_refs(layout, what, refs),
+        Action::Arrange(layouts) => {
+            for layout in layouts {
+                layout_refs(layout, what, refs);
+            }
+        }
+        Action::Macro(steps) => {
+            for step in steps {
+                action_refs(step, what, refs);
+            }
+        }
+        Action::SendToMonitor(MonitorTarget::Number(n))
+        | Action::MirrorToMonitor(MonitorTarget::Number(n)) => {
+            refs.monitors.push((what.to_owned(), n.to_string()))
+        }
+        Action::SendToMonitor(MonitorTarget::Name(name))
+        | Action::MirrorToMonitor(MonitorTarget::Name(name)) => {
+            refs.monitors.push((what.to_owned(), name.clone()))
+        }
+        _ => {}
+    }
+}
+
+fn layout_refs(layout: &Layout, what: &str, refs: &mut ScreenRefs) {
+    let Layout::Custom(layout) = layout else {
+        return;
+    };
+
+    if let Some(n) = layout.monitor {
+        refs.monitors.push((what.to_owned(), n.to_string()));
+    }
+
+    if layout.in_screen_coordinates() {
+        refs.layouts.push((what.to_owned(), *layout));
+    }
+}
+
+fn keybind_what(index: usize, layer: Option<&str>) -> String {
+    match layer {
+        Some(name) => format!("Keybind {index} of layer {name}"),
+        None => format!("Keybind {index}"),
+    }
+}
+
+fn check_keybinds(keybinds: &[KeyBind], layer: Option<&str>, problems: &mut Vec<String>) {
+    let mut valid = true;
+
+    for (index, keybind) in keybinds.iter().enumerate() {
+        let what = keybind_what(index, layer);
+
+        valid &= check_modifiers(&keybind.modifiers, &what, problems);
+        valid &= check_key(&keybind.key, &what, problems);
+
+        if keybind.action.is_none() && keybind.cycle.is_empty() && keybind.chord.is_empty() {
+            problems.push(format!("{what} has no layout, action, cycle or chord"));
+        }
+
+        if let Some(action) = &keybind.action {
+            check_action(action, &what, problems);
+        }
+
+        if let Some(when) = &keybind.when {
+            check_window_match(when, &what, problems);
+        }
+
+        for layout in &keybind.cycle {
+            check_layout(layout, &what, problems);
+        }
+
+        for (offset, bind) in keybind.chord.iter().enumerate() {
+            let what = format!("{what}, chord key {offset}");
+
+            valid &= check_modifiers(&bind.modifiers, &what, problems);
+            valid &= check_key(&bind.key, &what, problems);
+            check_action(&bind.action, &what, problems);
+        }
+    }
+
+    // Combos can only be compared once every key and modifier is known
+    if valid {
+        problems.extend(
+            find_conflicts(keybinds)
+                .into_iter()
+                .map(|conflict| match layer {
+                    Some(name) => format!("Layer {name}: {conflict}"),
+                    None => conflict,
+                }),
+        );
+    }
+}
+
+fn check_modifiers(modifiers: &[HexModifier], what: &str, problems: &mut Vec<String>) -> bool {
+    let known = MOD_ALT | MOD_CONTROL | MOD_SHIFT | MOD_WIN | MOD_NOREPEAT;
+    let mut valid = true;
+
+    for modifier in modifiers {
+        match modifier.parse() {
+            Some(HOT_KEY_MODIFIERS(bits)) if bits & !known.0 == 0 => {}
+            _ => {
+                problems.push(format!("{what} has an unknown modifier \"{}\"", modifier.0));
+                valid = false;
+            }
+        }
+    }
+
+    valid
+}
+
+fn check_key(key: &HexVirtualKey, what: &str, problems: &mut Vec<String>) -> bool {
+    match key.parse() {
+        Ok(vk) if vk.0 != 0 && vk.0 <= 0xFF => true,
+        Ok(_) => {
+            problems.push(format!("{what} has an unknown key \"{}\"", key.0));
+            false
+        }
+        Err(err) => {
+            problems.push(format!("{what} has an {err}"));
+            false
+        }
+    }
+}
+
+fn check_window_match(window: &WindowMatch, what: &str, problems: &mut Vec<String>) {
+    if let Some(title) = &window.title
+        && let Err(err) = regex::Regex::new(title)
+    {
+        problems.push(format!("{what} has an invalid title regex: {err}"));
+    }
+}
+
+fn check_action(action: &Action, what: &str, problems: &mut Vec<String>) {
+    match action {
+        Action::ApplyLayout(layout) | Action::PushColumn(layout) | Action::StackOnto(layout) => {
+            check_layout(layout, what, problems)
+        }
+        Action::Arrange(layouts) => {
+            for layout in layouts {
+                check_layout(layout, what, problems);
+            }
+        }
+        Action::Macro(steps) => {
+            for step in steps {
+                check_action(step, what, problems);
+            }
+        }
+        Action::SendToMonitor(MonitorTarget::Number(0))
+        | Action::MirrorToMonitor(MonitorTarget::Number(0)) => {
+            problems.push(format!("{what} sends to monitor 0, monitors count from 1"));
+        }
+        Action::SwitchDesktop(0) | Action::SendToDesktop(0) | Action::SendToDesktopAndFollow(0) => {
+            problems.push(format!("{what} names desktop 0, desktops count from 1"));
+        }
+        Action::OpacitySet(percent) if *percent > 100 => {
+            problems.push(format!("{what} sets an opacity above 100%"));
+        }
+        _ => {}
+    }
+}
+
+fn check_layout(layout: &Layout, what: &str, problems: &mut Vec<String>) {
+    match layout {
+        Layout::Custom(layout) => {
+            if layout.w <= 0 || layout.h <= 0 {
+                problems.push(format!(
+                    "{what} has a custom layout with no size ({}x{})",
+                    layout.w, layout.h
+                ));
+            }
+
+            if layout.unit == Unit::Pixels
+                && layout.measured_from() == Some(Origin::VirtualScreen)
+                && (layout.x < 0 || layout.y < 0)
+            {
+                problems.push(format!(
+                    "{what} has a layout measured from the top-left corner of all monitors \
+                     with negative coordinates, which are off-screen"
+                ));
+            }
+
+            let fits = |start: i32, size: i32| start >= 0 && start + size <= 100;
+
+            if layout.unit == Unit::Percent
+                && !(fits(layout.x, layout.w) && fits(layout.y, layout.h))
+            {
+                problems.push(format!("{what} has a percent layout that goes past 100%"));
+            }
+        }
+        Layout::Grid(GridLayout {
+            cols,
+            rows,
+            col,
+            row,
+            col_span,
+            row_span,
+        }) => {
+            if col.saturating_add((*col_span).max(1)) > *cols {
+                problems.push(format!(
+                    "{what} has a grid layout that doesn't fit in {cols} columns"
+                ));
+            }
+
+            if row.saturating_add((*row_span).max(1)) > *rows {
+                problems.push(format!(
+                    "{what} has a grid layout that doesn't fit in {rows} rows"
+                ));
+            }
+        }
+        Layout::Default(_) => {}
+    }
+}
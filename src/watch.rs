@@ -0,0 +1,57 @@
+//! Watching the config file so edits apply without a restart.
+//!
+//! Change notifications only cover whole directories, and editors tend to
+//! write a file several times per save, so the message loop gets a message per
+//! change and decides for itself whether the file is worth reloading.
+
+use std::{path::Path, thread};
+
+use windows::{
+    Win32::{
+        Foundation::{LPARAM, WAIT_OBJECT_0, WPARAM},
+        Storage::FileSystem::{
+            FILE_NOTIFY_CHANGE_FILE_NAME, FILE_NOTIFY_CHANGE_LAST_WRITE,
+            FindCloseChangeNotification, FindFirstChangeNotificationW, FindNextChangeNotification,
+        },
+        System::Threading::{GetCurrentThreadId, INFINITE, WaitForSingleObject},
+        UI::WindowsAndMessaging::{PostThreadMessageW, WM_APP},
+    },
+    core::HSTRING,
+};
+
+/// Posted to the watching thread's caller when something in the config
+/// file's directory changed
+pub const WM_APP_CONFIG_CHANGED: u32 = WM_APP + 4;
+
+/// Watch the directory of the config file on a background thread, posting
+/// `WM_APP_CONFIG_CHANGED` to the calling thread on every change
+pub unsafe fn watch_config(path: &Path) {
+    let Some(dir) = path.parent() else {
+        return;
+    };
+
+    let dir = HSTRING::from(dir);
+    let thread_id = GetCurrentThreadId();
+
+    thread::spawn(move || unsafe {
+        let filter = FILE_NOTIFY_CHANGE_LAST_WRITE | FILE_NOTIFY_CHANGE_FILE_NAME;
+
+        let handle = match FindFirstChangeNotificationW(&dir, false, filter) {
+            Ok(handle) => handle,
+            Err(err) => {
+                eprintln!("Failed to watch config directory: {err}");
+                return;
+            }
+        };
+
+        while WaitForSingleObject(handle, INFINITE) == WAIT_OBJECT_0 {
+            let _ = PostThreadMessageW(thread_id, WM_APP_CONFIG_CHANGED, WPARAM(0), LPARAM(0));
+
+            if FindNextChangeNotification(handle).is_err() {
+                break;
+            }
+        }
+
+        let _ = FindCloseChangeNotification(handle);
+    });
+}
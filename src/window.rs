@@ -0,0 +1,207 @@
+//! Queries about top-level windows
+
+use windows::{
+    Win32::{
+        Foundation::{CloseHandle, HWND, LPARAM, MAX_PATH, RECT},
+        Graphics::{
+            Dwm::{DWMWA_CLOAKED, DwmGetWindowAttribute},
+            Gdi::{MONITOR_DEFAULTTONEAREST, MonitorFromWindow},
+        },
+        System::Threading::{
+            OpenProcess, PROCESS_NAME_WIN32, PROCESS_QUERY_LIMITED_INFORMATION,
+            QueryFullProcessImageNameW,
+        },
+        UI::WindowsAndMessaging::{
+            EnumWindows, GW_OWNER, GWL_EXSTYLE, GetClassNameW, GetWindow, GetWindowLongPtrW,
+            GetWindowRect, GetWindowTextLengthW, GetWindowTextW, GetWindowThreadProcessId,
+            IsIconic, IsWindowVisible, WINDOW_EX_STYLE, WS_EX_TOOLWINDOW,
+        },
+    },
+    core::{BOOL, PWSTR},
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum Direction {
+    Left,
+    Right,
+    Up,
+    Down,
+}
+
+/// Image file name of the process that owns the window, e.g. `Code.exe`
+pub unsafe fn process_name(hwnd: HWND) -> Option<String> {
+    let path = process_path(hwnd)?;
+
+    path.rsplit('\\').next().map(str::to_owned)
+}
+
+/// Id of the process that owns the window
+pub unsafe fn process_id(hwnd: HWND) -> Option<u32> {
+    let mut pid = 0u32;
+
+    GetWindowThreadProcessId(hwnd, Some(&raw mut pid));
+
+    (pid != 0).then_some(pid)
+}
+
+/// Full image path of the process that owns the window
+pub unsafe fn process_path(hwnd: HWND) -> Option<String> {
+    let pid = process_id(hwnd)?;
+
+    let process = OpenProcess(PROCESS_QUERY_LIMITED_INFORMATION, false, pid).ok()?;
+
+    let mut buf = [0u16; MAX_PATH as usize];
+    let mut len = buf.len() as u32;
+
+    let result = QueryFullProcessImageNameW(
+        process,
+        PROCESS_NAME_WIN32,
+        PWSTR(buf.as_mut_ptr()),
+        &mut len,
+    );
+
+    let _ = CloseHandle(process);
+
+    result.ok()?;
+
+    Some(String::from_utf16_lossy(&buf[..len as usize]))
+}
+
+pub unsafe fn title(hwnd: HWND) -> String {
+    let mut buf = vec![0u16; GetWindowTextLengthW(hwnd).max(0) as usize + 1];
+    let len = GetWindowTextW(hwnd, &mut buf).max(0) as usize;
+
+    String::from_utf16_lossy(&buf[..len])
+}
+
+pub unsafe fn class_name(hwnd: HWND) -> String {
+    let mut buf = [0u16; 256];
+    let len = GetClassNameW(hwnd, &mut buf).max(0) as usize;
+
+    String::from_utf16_lossy(&buf[..len])
+}
+
+pub unsafe fn rect(hwnd: HWND) -> Option<RECT> {
+    let mut rect = RECT::default();
+
+    GetWindowRect(hwnd, &mut rect).ok()?;
+
+    Some(rect)
+}
+
+/// Top-level windows a user would consider open (roughly what Alt+Tab shows),
+/// in z-order from top to bottom. Minimized windows are included.
+pub unsafe fn app_windows() -> Vec<HWND> {
+    let mut windows: Vec<HWND> = Vec::new();
+
+    let _ = EnumWindows(Some(collect_app_window), LPARAM(&raw mut windows as isize));
+
+    windows
+}
+
+/// Every visible top-level window, in z-order from top to bottom
+pub unsafe fn visible_windows() -> Vec<HWND> {
+    let mut windows: Vec<HWND> = Vec::new();
+
+    let _ = EnumWindows(
+        Some(collect_visible_window),
+        LPARAM(&raw mut windows as isize),
+    );
+
+    windows
+}
+
+/// Non-minimized app windows on the same monitor as `hwnd`, in z-order
+pub unsafe fn same_monitor(hwnd: HWND) -> Vec<HWND> {
+    let monitor = MonitorFromWindow(hwnd, MONITOR_DEFAULTTONEAREST);
+
+    app_windows()
+        .into_iter()
+        .filter(|&other| {
+            !IsIconic(other).as_bool()
+                && MonitorFromWindow(other, MONITOR_DEFAULTTONEAREST) == monitor
+        })
+        .collect()
+}
+
+unsafe extern "system" fn collect_app_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let windows = &mut *(lparam.0 as *mut Vec<HWND>);
+
+    if is_app_window(hwnd) {
+        windows.push(hwnd);
+    }
+
+    true.into()
+}
+
+unsafe extern "system" fn collect_visible_window(hwnd: HWND, lparam: LPARAM) -> BOOL {
+    let windows = &mut *(lparam.0 as *mut Vec<HWND>);
+
+    if IsWindowVisible(hwnd).as_bool() {
+        windows.push(hwnd);
+    }
+
+    true.into()
+}
+
+pub unsafe fn is_app_window(hwnd: HWND) -> bool {
+    if !IsWindowVisible(hwnd).as_bool() || GetWindowTextLengthW(hwnd) == 0 {
+        return false;
+    }
+
+    // Dialogs and other owned windows follow their owner
+    if GetWindow(hwnd, GW_OWNER).is_ok_and(|owner| !owner.is_invalid()) {
+        return false;
+    }
+
+    let ex_style = WINDOW_EX_STYLE(GetWindowLongPtrW(hwnd, GWL_EXSTYLE) as u32);
+
+    if ex_style.contains(WS_EX_TOOLWINDOW) {
+        return false;
+    }
+
+    !is_cloaked(hwnd)
+}
+
+/// Cloaked windows are "visible" but not shown, e.g. suspended UWP apps and
+/// windows on other virtual desktops
+pub unsafe fn is_cloaked(hwnd: HWND) -> bool {
+    let mut cloaked = 0u32;
+
+    DwmGetWindowAttribute(
+        hwnd,
+        DWMWA_CLOAKED,
+        (&raw mut cloaked).cast(),
+        size_of::<u32>() as u32,
+    )
+    .is_ok()
+        && cloaked != 0
+}
+
+fn center(rect: &RECT) -> (i32, i32) {
+    ((rect.left + rect.right) / 2, (rect.top + rect.bottom) / 2)
+}
+
+/// Nearest non-minimized app window in the given direction, measured between
+/// window centers. Windows off to the side count for more than ones straight ahead.
+pub unsafe fn neighbour(hwnd: HWND, direction: Direction) -> Option<HWND> {
+    let (fx, fy) = center(&rect(hwnd)?);
+
+    app_windows()
+        .into_iter()
+        .filter(|&other| other != hwnd && !IsIconic(other).as_bool())
+        .filter_map(|other| {
+            let (x, y) = center(&rect(other)?);
+
+            let (ahead, aside) = match direction {
+                Direction::Left => (fx - x, y - fy),
+                Direction::Right => (x - fx, y - fy),
+                Direction::Up => (fy - y, x - fx),
+                Direction::Down => (y - fy, x - fx),
+            };
+
+            (ahead > 0).then_some((other, ahead + 2 * aside.abs()))
+        })
+        .min_by_key(|(_, distance)| *distance)
+        .map(|(other, _)| other)
+}